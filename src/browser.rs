@@ -0,0 +1,83 @@
+//! Browser profiles detection
+
+use std::path::{Path, PathBuf};
+
+/// Directory holding the profiles of the given browser, same locations as yt-dlp
+fn profiles_dir(browser: &str) -> Option<PathBuf> {
+    if browser == "firefox" {
+        return if cfg!(target_os = "windows") {
+            dirs::config_dir().map(|d| d.join("Mozilla/Firefox/Profiles"))
+        } else if cfg!(target_os = "macos") {
+            dirs::config_dir().map(|d| d.join("Firefox/Profiles"))
+        } else {
+            dirs::home_dir().map(|d| d.join(".mozilla/firefox"))
+        };
+    }
+
+    let (base, dir) = if cfg!(target_os = "windows") {
+        match browser {
+            "brave" => (
+                dirs::data_local_dir(),
+                "BraveSoftware/Brave-Browser/User Data",
+            ),
+            "chrome" => (dirs::data_local_dir(), "Google/Chrome/User Data"),
+            "chromium" => (dirs::data_local_dir(), "Chromium/User Data"),
+            "edge" => (dirs::data_local_dir(), "Microsoft/Edge/User Data"),
+            "opera" => (dirs::config_dir(), "Opera Software/Opera Stable"),
+            "vivaldi" => (dirs::data_local_dir(), "Vivaldi/User Data"),
+            _ => return None,
+        }
+    } else if cfg!(target_os = "macos") {
+        match browser {
+            "brave" => (dirs::config_dir(), "BraveSoftware/Brave-Browser"),
+            "chrome" => (dirs::config_dir(), "Google/Chrome"),
+            "chromium" => (dirs::config_dir(), "Chromium"),
+            "edge" => (dirs::config_dir(), "Microsoft Edge"),
+            "opera" => (dirs::config_dir(), "com.operasoftware.Opera"),
+            "vivaldi" => (dirs::config_dir(), "Vivaldi"),
+            _ => return None,
+        }
+    } else {
+        match browser {
+            "brave" => (dirs::config_dir(), "BraveSoftware/Brave-Browser"),
+            "chrome" => (dirs::config_dir(), "google-chrome"),
+            "chromium" => (dirs::config_dir(), "chromium"),
+            "edge" => (dirs::config_dir(), "microsoft-edge"),
+            "opera" => (dirs::config_dir(), "opera"),
+            "vivaldi" => (dirs::config_dir(), "vivaldi"),
+            _ => return None,
+        }
+    };
+
+    base.map(|base| base.join(dir))
+}
+
+/// Whether the directory looks like a browser profile (ie. has a cookies database)
+fn is_profile(browser: &str, path: &Path) -> bool {
+    if browser == "firefox" {
+        path.join("cookies.sqlite").is_file()
+    } else {
+        path.join("Cookies").is_file() || path.join("Network/Cookies").is_file()
+    }
+}
+
+/// List the profiles names of the given browser, sorted
+///
+/// Returns an empty list when the detection isn't possible.
+pub fn profiles(browser: &str) -> Vec<String> {
+    let Some(dir) = profiles_dir(browser) else {
+        return Vec::new();
+    };
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Vec::new();
+    };
+
+    let mut profiles: Vec<String> = entries
+        .flatten()
+        .filter(|entry| is_profile(browser, &entry.path()))
+        .filter_map(|entry| entry.file_name().into_string().ok())
+        .collect();
+
+    profiles.sort_unstable();
+    profiles
+}
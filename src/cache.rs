@@ -0,0 +1,75 @@
+//! Info json cache
+
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+/// FNV-1a hash of the bytes, stable across builds unlike the std hasher
+pub fn fnv1a(bytes: impl IntoIterator<Item = u8>) -> u64 {
+    bytes.into_iter().fold(0xcbf29ce484222325u64, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+/// Directory of the cache entry for the given key, each entry holds the `.info.json` files
+///
+/// The key is made of the url and the arguments changing the fetched info json.
+fn entry_dir(key: &[&OsStr]) -> Option<PathBuf> {
+    let hash = fnv1a(
+        key.iter()
+            .flat_map(|part| part.as_encoded_bytes().iter().copied().chain([0])),
+    );
+
+    dirs::cache_dir().map(|dir| dir.join("md").join(format!("{hash:016x}")))
+}
+
+/// Copy the cached `.info.json` files of the key into `dir`, if fresher than `ttl`
+///
+/// Returns whether the cache was used, any error being a cache miss.
+pub fn restore(key: &[&OsStr], ttl: Duration, dir: &Path) -> bool {
+    let Some(entry_dir) = entry_dir(key) else {
+        return false;
+    };
+
+    let is_fresh = std::fs::metadata(&entry_dir)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+        .and_then(|modified| SystemTime::now().duration_since(modified).ok())
+        .is_some_and(|age| age <= ttl);
+    if !is_fresh {
+        return false;
+    }
+
+    let copy = || -> std::io::Result<()> {
+        for entry in std::fs::read_dir(&entry_dir)? {
+            let entry = entry?;
+            std::fs::copy(entry.path(), dir.join(entry.file_name()))?;
+        }
+        Ok(())
+    };
+
+    copy().is_ok()
+}
+
+/// Store the `.info.json` files of `dir` as the cache entry of the key
+pub fn store(key: &[&OsStr], dir: &Path) -> std::io::Result<()> {
+    let Some(entry_dir) = entry_dir(key) else {
+        return Ok(());
+    };
+
+    if entry_dir.exists() {
+        std::fs::remove_dir_all(&entry_dir)?;
+    }
+    std::fs::create_dir_all(&entry_dir)?;
+
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_file() && path.to_string_lossy().ends_with(".info.json") {
+            if let Some(file_name) = path.file_name() {
+                std::fs::copy(&path, entry_dir.join(file_name))?;
+            }
+        }
+    }
+
+    Ok(())
+}
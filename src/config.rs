@@ -0,0 +1,284 @@
+//! Config file
+
+use std::collections::BTreeMap;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context};
+use chrono::NaiveTime;
+use serde::Deserialize;
+
+use crate::{parse, Codec, Container, Preset};
+
+/// Persistent defaults, the CLI flags take precedence over them
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Preset selected by default in the preset prompt
+    pub default_preset: Option<Preset>,
+    /// Preset selected by default per site, by extractor key, like `Youtube = "best"`
+    pub site_presets: Option<BTreeMap<String, Preset>>,
+    /// Default answer of the "Embed thumbnail?" prompt
+    pub embed_thumbnail: Option<bool>,
+    /// Default answer of the "Embed chapters?" prompt
+    pub embed_chapters: Option<bool>,
+    /// Use XDG-dirs, like `--dirs`
+    pub use_dirs: Option<bool>,
+    /// Embed the metadata, like `--embed-metadata` or `--no-embed-metadata`
+    pub embed_metadata: Option<bool>,
+    /// Replacements applied to the title in the file name, like `"/" = "-"`
+    pub title_replacements: Option<BTreeMap<String, String>>,
+    /// Named profiles, like `[profile.phone]`, selected with `--profile`
+    pub profile: Option<BTreeMap<String, Profile>>,
+    /// Download rates by time of day, like `["09:00-17:00=2M"]`, overridden by `--limit-rate`
+    pub rate_limits: Option<Vec<RateWindow>>,
+}
+
+/// Download rate limit applied during a time window, which can span midnight
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct RateWindow {
+    pub start: NaiveTime,
+    pub end: NaiveTime,
+    /// Rate like `--limit-rate`
+    pub rate: String,
+}
+
+impl TryFrom<String> for RateWindow {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        let invalid = || format!("invalid rate limit `{value}`, expected e.g. `09:00-17:00=2M`");
+
+        let (window, rate) = value.split_once('=').ok_or_else(invalid)?;
+        let (start, end) = window.split_once('-').ok_or_else(invalid)?;
+        let time =
+            |time: &str| NaiveTime::parse_from_str(time.trim(), "%H:%M").map_err(|_| invalid());
+
+        Ok(RateWindow {
+            start: time(start)?,
+            end: time(end)?,
+            rate: parse::rate(rate.trim())?,
+        })
+    }
+}
+
+impl RateWindow {
+    /// Whether the time is in the window, its end excluded
+    pub fn contains(&self, time: NaiveTime) -> bool {
+        if self.start <= self.end {
+            self.start <= time && time < self.end
+        } else {
+            self.start <= time || time < self.end
+        }
+    }
+}
+
+/// Bundle of settings layered over the config, the CLI flags still taking precedence
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Profile {
+    /// Overrides the `default_preset` config
+    pub default_preset: Option<Preset>,
+    /// Overrides the `embed_thumbnail` config
+    pub embed_thumbnail: Option<bool>,
+    /// Overrides the `embed_chapters` config
+    pub embed_chapters: Option<bool>,
+    /// Overrides the `use_dirs` config
+    pub use_dirs: Option<bool>,
+    /// Overrides the `embed_metadata` config
+    pub embed_metadata: Option<bool>,
+    /// Default of `--quality`
+    pub quality: Option<u32>,
+    /// Default of `--prefer-codec`
+    pub prefer_codec: Option<Codec>,
+    /// Default of `--prefer-container`
+    pub prefer_container: Option<Container>,
+    /// Default of `--merge-output-format`
+    pub merge_output_format: Option<Container>,
+    /// Default of `--max-filesize`, like `"500MB"`
+    pub max_filesize: Option<String>,
+}
+
+impl Config {
+    /// Default path of the config file (`~/.config/md/config.toml` on Linux)
+    pub fn default_path() -> Option<PathBuf> {
+        dirs::config_dir().map(|dir| dir.join("md").join("config.toml"))
+    }
+
+    /// Preset selected by default for the site, falling back to the default preset
+    pub fn preset_for(&self, extractor_key: &str) -> Option<Preset> {
+        self.site_presets
+            .iter()
+            .flatten()
+            .find(|(key, _)| key.eq_ignore_ascii_case(extractor_key))
+            .map(|(_, &preset)| preset)
+            .or(self.default_preset)
+    }
+
+    /// Rate limit of the first window containing the time, if any
+    pub fn rate_limit_at(&self, time: NaiveTime) -> Option<&str> {
+        self.rate_limits
+            .iter()
+            .flatten()
+            .find(|window| window.contains(time))
+            .map(|window| window.rate.as_str())
+    }
+
+    /// Layer the named profile over the config, returning it for its defaults of the flags
+    ///
+    /// The values of the flags are validated like the flags themselves.
+    pub fn apply_profile(&mut self, name: &str) -> Result<Profile, anyhow::Error> {
+        let Some(profile) = self
+            .profile
+            .as_mut()
+            .and_then(|profiles| profiles.remove(name))
+        else {
+            let names: Vec<&str> = self
+                .profile
+                .iter()
+                .flatten()
+                .map(|(name, _)| name.as_str())
+                .collect();
+            if names.is_empty() {
+                bail!("unknown profile `{name}`, the config has no profiles");
+            }
+            bail!(
+                "unknown profile `{name}`, expected one of: {}",
+                names.join(", ")
+            );
+        };
+
+        if let Some(quality) = profile.quality {
+            parse::quality(&quality.to_string())
+                .map_err(|err| anyhow::anyhow!("invalid profile `{name}`: {err}"))?;
+        }
+        if let Some(max_filesize) = &profile.max_filesize {
+            parse::filesize(max_filesize)
+                .map_err(|err| anyhow::anyhow!("invalid profile `{name}`: {err}"))?;
+        }
+
+        self.default_preset = profile.default_preset.or(self.default_preset);
+        self.embed_thumbnail = profile.embed_thumbnail.or(self.embed_thumbnail);
+        self.embed_chapters = profile.embed_chapters.or(self.embed_chapters);
+        self.use_dirs = profile.use_dirs.or(self.use_dirs);
+        self.embed_metadata = profile.embed_metadata.or(self.embed_metadata);
+
+        Ok(profile)
+    }
+
+    /// Load the config file, a missing file being the default config
+    pub fn load(path: &Path) -> Result<Config, anyhow::Error> {
+        let config = match std::fs::read_to_string(path) {
+            Ok(config) => config,
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(Config::default()),
+            Err(err) => {
+                return Err(err)
+                    .with_context(|| format!("unable to read the config file: {}", path.display()))
+            }
+        };
+
+        toml::from_str(&config)
+            .with_context(|| format!("unable to parse the config file: {}", path.display()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn readme_example_is_valid() {
+        let readme = include_str!("../README.md");
+        let example = readme
+            .split("```toml\n")
+            .nth(1)
+            .and_then(|rest| rest.split("```").next())
+            .unwrap();
+
+        let config: Config = toml::from_str(example).unwrap();
+        assert_eq!(config.default_preset, Some(Preset::Best));
+        assert_eq!(config.preset_for("soundcloud"), Some(Preset::BestAudio));
+        assert_eq!(config.preset_for("Vimeo"), Some(Preset::Best));
+        assert_eq!(config.title_replacements.unwrap()["/"], "-");
+    }
+
+    #[test]
+    fn unknown_keys_are_rejected() {
+        let err = toml::from_str::<Config>("embed_thumbnails = true").unwrap_err();
+        assert!(err.to_string().contains("unknown field `embed_thumbnails`"));
+    }
+
+    #[test]
+    fn profiles_are_layered_over_the_config() {
+        let mut config: Config = toml::from_str(
+            r#"
+            default_preset = "best-audio"
+            embed_chapters = false
+            use_dirs = true
+
+            [profile.phone]
+            default_preset = "best"
+            quality = 720
+            merge_output_format = "mp4"
+            "#,
+        )
+        .unwrap();
+
+        let profile = config.apply_profile("phone").unwrap();
+        assert_eq!(config.default_preset, Some(Preset::Best));
+        assert_eq!(config.embed_chapters, Some(false));
+        assert_eq!(config.use_dirs, Some(true));
+        assert_eq!(profile.quality, Some(720));
+        assert_eq!(profile.merge_output_format, Some(Container::Mp4));
+    }
+
+    #[test]
+    fn unknown_and_invalid_profiles_are_rejected() {
+        let mut config: Config = toml::from_str(
+            r#"
+            [profile.tv]
+            quality = 900
+
+            [profile.tiny]
+            max_filesize = "1 byte"
+            "#,
+        )
+        .unwrap();
+
+        let err = |config: &mut Config, name| config.apply_profile(name).unwrap_err().to_string();
+        assert_eq!(
+            err(&mut config, "phone"),
+            "unknown profile `phone`, expected one of: tiny, tv"
+        );
+        assert!(err(&mut config, "tv").starts_with("invalid profile `tv`: unsupported quality"));
+        assert!(err(&mut config, "tiny").starts_with("invalid profile `tiny`:"));
+        assert_eq!(
+            err(&mut Config::default(), "phone"),
+            "unknown profile `phone`, the config has no profiles"
+        );
+    }
+
+    #[test]
+    fn rate_limit_of_the_time_of_day() {
+        let config: Config =
+            toml::from_str(r#"rate_limits = ["09:00-17:00=2M", "22:00-07:00=500K"]"#).unwrap();
+        let at =
+            |time: &str| config.rate_limit_at(NaiveTime::parse_from_str(time, "%H:%M").unwrap());
+
+        assert_eq!(at("09:00"), Some("2M"));
+        assert_eq!(at("16:59"), Some("2M"));
+        assert_eq!(at("17:00"), None);
+        assert_eq!(at("23:30"), Some("500K"));
+        assert_eq!(at("03:00"), Some("500K"));
+        assert_eq!(at("07:00"), None);
+    }
+
+    #[test]
+    fn invalid_rate_limits_are_rejected() {
+        for rate_limit in ["09:00=2M", "9h-17h=2M", "09:00-17:00", "09:00-17:00=2X"] {
+            let config = format!("rate_limits = [\"{rate_limit}\"]");
+            assert!(toml::from_str::<Config>(&config).is_err(), "{rate_limit}");
+        }
+    }
+}
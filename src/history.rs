@@ -0,0 +1,84 @@
+//! Title edits history
+
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+
+/// Maximum number of channels remembered
+const MAX_ENTRIES: usize = 200;
+
+/// Last title edit of each channel, most recent first
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct TitleHistory {
+    entries: Vec<TitleEdit>,
+}
+
+/// Title edit, as the text removed around the original title
+#[derive(Debug, Serialize, Deserialize)]
+struct TitleEdit {
+    channel_id: String,
+    strip_prefix: String,
+    strip_suffix: String,
+}
+
+impl TitleHistory {
+    fn path() -> Option<PathBuf> {
+        dirs::cache_dir().map(|dir| dir.join("md").join("title-history.json"))
+    }
+
+    /// Load the history, any error being an empty history
+    pub fn load() -> TitleHistory {
+        Self::path()
+            .and_then(|path| std::fs::read(path).ok())
+            .and_then(|history| serde_json::from_slice(&history).ok())
+            .unwrap_or_default()
+    }
+
+    /// Save the history in the cache directory
+    pub fn save(&self) -> Result<(), anyhow::Error> {
+        let Some(path) = Self::path() else {
+            return Ok(());
+        };
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, serde_json::to_vec(self)?)?;
+        Ok(())
+    }
+
+    /// Apply the last edit of the channel to the title, if it still applies
+    pub fn apply(&self, channel_id: &str, title: &str) -> Option<String> {
+        let edit = self.entries.iter().find(|e| e.channel_id == channel_id)?;
+
+        title
+            .strip_prefix(&edit.strip_prefix)?
+            .strip_suffix(&edit.strip_suffix)
+            .map(|title| title.trim().to_string())
+            .filter(|title| !title.is_empty())
+    }
+
+    /// Remember how the original title was edited for the channel
+    ///
+    /// Only edits removing text around the title can be replayed, others are forgotten.
+    pub fn record(&mut self, channel_id: &str, original: &str, edited: &str) {
+        self.entries.retain(|e| e.channel_id != channel_id);
+
+        let edited = edited.trim();
+        if edited.is_empty() || edited == original {
+            return;
+        }
+        let Some(start) = original.find(edited) else {
+            return;
+        };
+
+        self.entries.insert(
+            0,
+            TitleEdit {
+                channel_id: channel_id.to_string(),
+                strip_prefix: original[..start].to_string(),
+                strip_suffix: original[start + edited.len()..].to_string(),
+            },
+        );
+        self.entries.truncate(MAX_ENTRIES);
+    }
+}
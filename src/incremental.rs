@@ -0,0 +1,80 @@
+//! Incremental downloads state, per url
+
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+
+use crate::cache;
+
+/// Newest upload date downloaded from an url, with the videos of that date
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct State {
+    /// Upload date as `YYYYMMDD`, `None` before the first run
+    pub date: Option<String>,
+    /// Archive entries (`extractor id`) of the videos of that date already downloaded
+    pub entries: Vec<String>,
+}
+
+impl State {
+    fn path(url: &str) -> Option<PathBuf> {
+        dirs::data_local_dir().map(|dir| {
+            dir.join("md")
+                .join("incremental")
+                .join(format!("{:016x}.json", cache::fnv1a(url.bytes())))
+        })
+    }
+
+    /// Load the state of the url, any error being the state of the first run
+    pub fn load(url: &str) -> State {
+        Self::path(url)
+            .and_then(|path| std::fs::read(path).ok())
+            .and_then(|state| serde_json::from_slice(&state).ok())
+            .unwrap_or_default()
+    }
+
+    /// Save the state of the url in the data directory
+    pub fn save(&self, url: &str) -> Result<(), anyhow::Error> {
+        let Some(path) = Self::path(url) else {
+            return Ok(());
+        };
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, serde_json::to_vec(self)?)?;
+        Ok(())
+    }
+
+    /// Remember a downloaded video, only the newest upload date being kept
+    pub fn record(&mut self, archive_entry: &str, upload_date: Option<&str>) {
+        let Some(upload_date) = upload_date else {
+            return;
+        };
+
+        match self.date.as_deref() {
+            Some(date) if upload_date < date => {}
+            Some(date) if upload_date == date => self.entries.push(archive_entry.to_string()),
+            _ => {
+                self.date = Some(upload_date.to_string());
+                self.entries = vec![archive_entry.to_string()];
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_the_newest_date_is_kept() {
+        let mut state = State::default();
+        state.record("youtube a", Some("20230510"));
+        state.record("youtube b", Some("20230512"));
+        state.record("youtube c", Some("20230512"));
+        state.record("youtube d", Some("20230501"));
+        state.record("youtube e", None);
+
+        assert_eq!(state.date.as_deref(), Some("20230512"));
+        assert_eq!(state.entries, ["youtube b", "youtube c"]);
+    }
+}
@@ -16,6 +16,7 @@ pub struct InfoJson {
     pub uploader_url: Option<String>,
     pub channel_id: Option<String>,
     pub channel_url: Option<String>,
+    #[serde(default, deserialize_with = "lenient_duration")]
     pub duration: Option<f64>,
     pub view_count: Option<i64>,
     pub age_limit: Option<i64>,
@@ -26,11 +27,15 @@ pub struct InfoJson {
     // pub live_status: Option<String>,
     pub automatic_captions: Option<HashMap<String, Vec<AutomaticCaptionInfo>>>,
     pub subtitles: Option<HashMap<String, Subtitles>>,
+    pub chapters: Option<Vec<Chapter>>,
     pub comment_count: Option<i64>,
     pub like_count: Option<i64>,
     pub channel: Option<String>,
     pub channel_follower_count: Option<i64>,
     pub upload_date: Option<String>,
+    pub playlist_index: Option<i64>,
+    pub timestamp: Option<f64>,
+    pub release_timestamp: Option<f64>,
     pub availability: Option<String>,
     // pub webpage_url_basename: String,
     // pub webpage_url_domain: String,
@@ -41,16 +46,16 @@ pub struct InfoJson {
     pub duration_string: Option<String>,
     pub is_live: Option<bool>,
     pub was_live: Option<bool>,
-    pub format: String,
-    pub format_id: String,
-    pub ext: String,
-    pub protocol: String,
+    pub format: Option<String>,
+    pub format_id: Option<String>,
+    pub ext: Option<String>,
+    pub protocol: Option<String>,
     pub format_note: Option<String>,
     pub filesize_approx: Option<i64>,
-    pub tbr: f64,
-    pub width: i64,
-    pub height: i64,
-    pub resolution: String,
+    pub tbr: Option<f64>,
+    pub width: Option<i64>,
+    pub height: Option<i64>,
+    pub resolution: Option<String>,
     pub fps: Option<f64>,
     pub dynamic_range: Option<String>,
     #[serde(deserialize_with = "lit_none_string")]
@@ -71,6 +76,13 @@ pub struct InfoJson {
     pub version: Version,
 }
 
+impl InfoJson {
+    /// Most precise release time available, as a Unix timestamp
+    pub fn precise_timestamp(&self) -> Option<f64> {
+        self.release_timestamp.or(self.timestamp)
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct AutomaticCaptionInfo {
     pub ext: String,
@@ -101,7 +113,10 @@ pub struct LiveCaptionInfo {
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Format {
+    #[serde(default)]
     pub format_id: String,
+    #[serde(deserialize_with = "lit_none_string")]
+    #[serde(default)]
     pub format_note: Option<String>,
     pub ext: String,
     pub protocol: String,
@@ -122,9 +137,14 @@ pub struct Format {
     #[serde(default)]
     pub resolution: Option<String>,
     pub aspect_ratio: Option<f64>,
-    // pub http_headers: Option<HashMap<String, String>>,
-    pub audio_ext: String,
-    pub video_ext: String,
+    #[serde(default)]
+    pub http_headers: Option<HashMap<String, String>>,
+    #[serde(deserialize_with = "lit_none_string")]
+    #[serde(default)]
+    pub audio_ext: Option<String>,
+    #[serde(deserialize_with = "lit_none_string")]
+    #[serde(default)]
+    pub video_ext: Option<String>,
     pub format: String,
     pub asr: Option<i64>,
     pub filesize: Option<u64>,
@@ -135,6 +155,8 @@ pub struct Format {
     pub tbr: Option<f64>,
     pub language_preference: Option<i64>,
     pub abr: Option<f64>,
+    #[serde(deserialize_with = "lit_none_string")]
+    #[serde(default)]
     pub container: Option<String>,
     pub preference: Option<i64>,
     pub dynamic_range: Option<String>,
@@ -142,10 +164,68 @@ pub struct Format {
     pub filesize_approx: Option<i64>,
 }
 
+impl Format {
+    /// Whether the format is a storyboard (a grid of thumbnails) rather than real media
+    pub fn is_storyboard(&self) -> bool {
+        self.rows.is_some() || self.columns.is_some()
+    }
+
+    /// Whether the format is downloaded in fragments, like the HLS and DASH ones
+    pub fn is_fragmented(&self) -> bool {
+        self.fragments.is_some() || self.protocol.contains("m3u8") || self.protocol.contains("dash")
+    }
+
+    /// File size, exact or approximate when that's all that is known
+    pub fn size(&self) -> Option<u64> {
+        self.filesize.or(self
+            .filesize_approx
+            .and_then(|size| u64::try_from(size).ok()))
+    }
+
+    /// Short quality label, like `1080p60 av01` for video or `opus 160k` for audio
+    pub fn quality_label(&self) -> String {
+        let short_codec = |codec: &str| codec.split('.').next().unwrap_or(codec).to_string();
+
+        if let Some(vcodec) = &self.vcodec {
+            let mut label = match (self.height, &self.resolution) {
+                (Some(height), _) => format!("{height}p"),
+                (None, Some(resolution)) => resolution.clone(),
+                (None, None) => String::new(),
+            };
+            if let (Some(_), Some(fps)) = (self.height, self.fps) {
+                if fps > 30.0 {
+                    label.push_str(&format!("{fps:.0}"));
+                }
+            }
+            if !label.is_empty() {
+                label.push(' ');
+            }
+            label.push_str(&short_codec(vcodec));
+            label
+        } else if let Some(acodec) = &self.acodec {
+            let mut label = short_codec(acodec);
+            if let Some(abr) = self.abr {
+                label.push_str(&format!(" {abr:.0}k"));
+            }
+            label
+        } else {
+            self.format_id.clone()
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Chapter {
+    pub start_time: f64,
+    pub end_time: f64,
+    pub title: Option<String>,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Fragment {
     pub url: Option<String>,
     pub path: Option<String>,
+    #[serde(default, deserialize_with = "lenient_duration")]
     pub duration: Option<f64>,
 }
 
@@ -159,6 +239,51 @@ pub struct Thumbnail {
     pub resolution: Option<String>,
 }
 
+/// Either a single video or the metadata of a playlist, as written by `--write-info-json`
+#[derive(Debug)]
+pub enum AnyInfoJson {
+    Video(Box<InfoJson>),
+    Playlist(Playlist),
+}
+
+impl<'de> Deserialize<'de> for AnyInfoJson {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let value = serde_json::Value::deserialize(deserializer)?;
+
+        if value.get("_type").and_then(|t| t.as_str()) == Some("playlist") {
+            Playlist::deserialize(value)
+                .map(AnyInfoJson::Playlist)
+                .map_err(serde::de::Error::custom)
+        } else {
+            InfoJson::deserialize(value)
+                .map(|info_json| AnyInfoJson::Video(Box::new(info_json)))
+                .map_err(serde::de::Error::custom)
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Playlist {
+    pub id: String,
+    pub title: Option<String>,
+    #[serde(default)]
+    pub entries: Vec<PlaylistEntry>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PlaylistEntry {
+    pub id: String,
+    pub url: String,
+    pub title: Option<String>,
+    #[serde(default, deserialize_with = "lenient_duration")]
+    pub duration: Option<f64>,
+    pub channel: Option<String>,
+    pub uploader: Option<String>,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Version {
     pub version: String,
@@ -174,3 +299,190 @@ where
 
     Ok(inner.and_then(|r| if r != "none" { Some(r) } else { None }))
 }
+
+/// Duration in seconds, as a number, a numeric string or null depending on the extractor
+fn lenient_duration<'de, D>(deserializer: D) -> Result<Option<f64>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Duration {
+        Number(f64),
+        String(String),
+    }
+
+    Ok(match Option::<Duration>::deserialize(deserializer)? {
+        Some(Duration::Number(duration)) => Some(duration),
+        Some(Duration::String(duration)) => duration.trim().parse().ok(),
+        None => None,
+    }
+    .filter(|duration: &f64| duration.is_finite() && *duration >= 0.0))
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use serde_json::json;
+
+    /// Smallest video info json yt-dlp could write, with a single audio format
+    pub(crate) fn minimal_video() -> serde_json::Value {
+        json!({
+            "id": "abc123",
+            "title": "Episode 1",
+            "formats": [{
+                "format_id": "mp3",
+                "ext": "mp3",
+                "protocol": "https",
+                "acodec": "mp3",
+                "vcodec": "none",
+                "format": "mp3 - audio only",
+            }],
+            "webpage_url": "https://podcast.example.org/episode-1",
+            "extractor": "generic",
+            "extractor_key": "Generic",
+            "display_id": "abc123",
+            "fulltitle": "Episode 1",
+            "epoch": 1683720000,
+            "_type": "video",
+            "_version": {
+                "version": "2023.03.04",
+                "release_git_head": "abcdef",
+                "repository": "yt-dlp/yt-dlp",
+            },
+        })
+    }
+
+    pub(crate) fn parse_video(value: serde_json::Value) -> InfoJson {
+        match serde_json::from_value(value).unwrap() {
+            AnyInfoJson::Video(info_json) => *info_json,
+            AnyInfoJson::Playlist(_) => panic!("parsed as a playlist"),
+        }
+    }
+
+    #[test]
+    fn audio_only_podcast_without_video_fields() {
+        let info_json = parse_video(minimal_video());
+
+        assert_eq!(info_json.width, None);
+        assert_eq!(info_json.height, None);
+        assert_eq!(info_json.resolution, None);
+        assert_eq!(info_json.tbr, None);
+        assert_eq!(info_json.format, None);
+        assert_eq!(info_json.format_id, None);
+        assert_eq!(info_json.ext, None);
+        assert_eq!(info_json.protocol, None);
+        assert_eq!(info_json.formats[0].acodec.as_deref(), Some("mp3"));
+        assert_eq!(info_json.formats[0].vcodec, None);
+    }
+
+    #[test]
+    fn duration_as_number_string_null_or_absent() {
+        let duration = |duration: Option<serde_json::Value>| {
+            let mut video = minimal_video();
+            if let Some(duration) = duration {
+                video["duration"] = duration;
+            }
+            parse_video(video).duration
+        };
+
+        assert_eq!(duration(Some(json!(754.5))), Some(754.5));
+        assert_eq!(duration(Some(json!("754"))), Some(754.0));
+        assert_eq!(duration(Some(json!(" 12.5 "))), Some(12.5));
+        assert_eq!(duration(Some(json!("live"))), None);
+        assert_eq!(duration(Some(json!(null))), None);
+        assert_eq!(duration(None), None);
+    }
+
+    #[test]
+    fn literal_none_strings_are_none() {
+        let mut video = minimal_video();
+        let format = &mut video["formats"][0];
+        format["container"] = json!("none");
+        format["format_note"] = json!("none");
+        format["audio_ext"] = json!("none");
+        format["video_ext"] = json!("none");
+
+        let info_json = parse_video(video);
+        let format = &info_json.formats[0];
+        assert_eq!(format.container, None);
+        assert_eq!(format.format_note, None);
+        assert_eq!(format.audio_ext, None);
+        assert_eq!(format.video_ext, None);
+    }
+
+    /// Format with the given fields, over the required ones
+    pub(crate) fn format(fields: serde_json::Value) -> Format {
+        let mut format = json!({ "ext": "mp4", "protocol": "https", "format": "" });
+        format
+            .as_object_mut()
+            .unwrap()
+            .extend(fields.as_object().unwrap().clone());
+        serde_json::from_value(format).unwrap()
+    }
+
+    #[test]
+    fn quality_label_of_video_with_high_fps() {
+        let format = format(json!({
+            "format_id": "399",
+            "vcodec": "av01.0.08M.08",
+            "acodec": "none",
+            "height": 1080,
+            "fps": 60.0,
+        }));
+        assert_eq!(format.quality_label(), "1080p60 av01");
+    }
+
+    #[test]
+    fn quality_label_of_video_at_30_fps() {
+        let format = format(json!({
+            "format_id": "137",
+            "vcodec": "avc1.640028",
+            "height": 1080,
+            "fps": 30.0,
+        }));
+        assert_eq!(format.quality_label(), "1080p avc1");
+    }
+
+    #[test]
+    fn quality_label_of_audio_with_abr() {
+        let format = format(json!({
+            "format_id": "251",
+            "vcodec": "none",
+            "acodec": "opus",
+            "abr": 160.2,
+        }));
+        assert_eq!(format.quality_label(), "opus 160k");
+    }
+
+    #[test]
+    fn quality_label_without_codecs() {
+        let format = format(json!({
+            "format_id": "hls-1080",
+            "vcodec": "none",
+            "acodec": "none",
+        }));
+        assert_eq!(format.quality_label(), "hls-1080");
+    }
+
+    #[test]
+    fn precise_timestamp_prefers_the_release_timestamp() {
+        let mut video = minimal_video();
+        assert_eq!(parse_video(video.clone()).precise_timestamp(), None);
+
+        video["timestamp"] = json!(1683720000);
+        assert_eq!(
+            parse_video(video.clone()).precise_timestamp(),
+            Some(1683720000.0)
+        );
+
+        video["release_timestamp"] = json!(1683716400.5);
+        assert_eq!(
+            parse_video(video.clone()).precise_timestamp(),
+            Some(1683716400.5)
+        );
+
+        video["timestamp"] = json!(null);
+        assert_eq!(parse_video(video).precise_timestamp(), Some(1683716400.5));
+    }
+}
@@ -1,13 +1,27 @@
-use std::{borrow::Cow, cmp::Reverse, fmt::Display, fs::File};
-use std::{io::BufReader, path::Path, process::Command};
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::process::{Command, ExitStatus, Stdio};
+use std::{borrow::Cow, cmp::Reverse, ffi::OsStr, fmt::Display, fs::File};
+use std::{io::BufReader, path::Path, path::PathBuf};
+use std::{io::IsTerminal, io::Write, sync::OnceLock, time::Duration};
 
 use anyhow::{bail, Context};
-use clap::{Parser, ValueEnum};
+use chrono::{DateTime, Local, NaiveDate};
+use clap::{parser::ValueSource, ArgMatches, CommandFactory, FromArgMatches, Parser, ValueEnum};
 use humansize::{SizeFormatter, BINARY};
-use inquire::{Confirm, MultiSelect, Select, Text};
+use indicatif::ProgressBar;
+use inquire::{validator::Validation, Confirm, MultiSelect, Select, Text};
+use serde::{Deserialize, Serialize};
 use tempfile::TempDir;
 
+mod browser;
+mod cache;
+mod config;
+mod history;
+mod incremental;
 mod infojson;
+mod parse;
+mod queue;
+mod shell;
 
 #[derive(Parser, Debug)]
 #[command(version, about, long_about = None)]
@@ -20,6 +34,10 @@ struct Args {
     #[arg(long)]
     quiet: bool,
 
+    /// Don't color the prompts, also disabled by a non-empty `NO_COLOR` environment variable
+    #[arg(long)]
+    no_color: bool,
+
     /// Preset to use
     #[arg(short, long, value_enum)]
     preset: Option<Preset>,
@@ -28,15 +46,360 @@ struct Args {
     #[arg(short, long)]
     dirs: bool,
 
-    /// Url of the media to download
-    url: String,
+    /// Directory to download into, created if missing [default: current directory]
+    #[arg(short, long, value_name = "PATH", conflicts_with = "dirs")]
+    output_dir: Option<PathBuf>,
+
+    /// Config file with persistent defaults [default: ~/.config/md/config.toml]
+    #[arg(long, value_name = "PATH")]
+    config: Option<PathBuf>,
+
+    /// Profile of the config to layer over it, like `phone` for its `[profile.phone]` table
+    #[arg(long, value_name = "NAME")]
+    profile: Option<String>,
+
+    /// Print the effective settings, with where each comes from, and exit
+    #[arg(long)]
+    config_dump: bool,
+
+    /// Load cookies from a browser (`browser[+keyring][:profile][::container]`)
+    #[arg(long, value_name = "BROWSER", value_parser = parse::cookies_from_browser)]
+    cookies_from_browser: Option<String>,
+
+    /// Load cookies from a Netscape formatted cookies file
+    #[arg(long, value_name = "FILE")]
+    cookies: Option<PathBuf>,
+
+    /// Abort the download when a fragment is unavailable
+    #[arg(long, conflicts_with = "skip_unavailable_fragments")]
+    abort_on_unavailable_fragments: bool,
+
+    /// Skip unavailable fragments (default)
+    #[arg(long)]
+    skip_unavailable_fragments: bool,
+
+    /// Number of fragments to download at once, with the fragmented (HLS, DASH) formats
+    #[arg(short = 'N', long, value_name = "N", value_parser = clap::value_parser!(u32).range(1..))]
+    concurrent_fragments: Option<u32>,
+
+    /// Print the raw info.json from yt-dlp to stdout and exit
+    #[arg(long)]
+    dump_json: bool,
+
+    /// Print a comparison of the top video formats before selecting (also with -vv)
+    #[arg(long)]
+    compare: bool,
+
+    /// Re-extract the video when the download rate drops below this (e.g. 100K)
+    #[arg(long, value_name = "RATE", value_parser = parse::rate)]
+    throttled_rate: Option<String>,
+
+    /// Maximum download rate, in bytes per second (e.g. 500K, 1M), over the config `rate_limits`
+    #[arg(long, value_name = "RATE", value_parser = parse::rate)]
+    limit_rate: Option<String>,
+
+    /// Print the download command as a reusable shell one-liner
+    ///
+    /// It downloads from the url of the media, the temporary info json being deleted.
+    #[arg(long)]
+    print_command: bool,
+
+    /// Start the video format selector at the first format at or below this height
+    #[arg(long, value_name = "HEIGHT")]
+    default_height: Option<i64>,
+
+    /// Subtitle format preference when embedding subtitles (e.g. ass/srt/best)
+    #[arg(long, value_name = "FORMAT", value_parser = parse::sub_format)]
+    sub_format: Option<String>,
+
+    /// Only fetch the metadata and report whether it resolved, without prompting or downloading
+    #[arg(long)]
+    simulate: bool,
+
+    /// Prefer formats that merge into this container without remuxing (preset mode)
+    #[arg(long, value_enum, value_name = "CONTAINER")]
+    prefer_container: Option<Container>,
+
+    /// Container to merge the video and audio into [default: mkv with the best preset]
+    #[arg(long, value_enum, value_name = "CONTAINER")]
+    merge_output_format: Option<Container>,
+
+    /// Relax the format filters instead of asking when they exclude every format
+    #[arg(long)]
+    lenient: bool,
+
+    /// Script rewriting the yt-dlp arguments, for both the metadata and download commands
+    ///
+    /// The script is called with the arguments and must print the new ones, one per line.
+    #[arg(long, value_name = "PATH")]
+    command_hook: Option<PathBuf>,
+
+    /// Print a JSON object describing the downloaded file after the download
+    #[arg(long)]
+    print_json_after: bool,
+
+    /// What to do with the downloaded file, asked when not given
+    #[arg(long, value_enum, value_name = "ACTION")]
+    after: Option<After>,
+
+    /// Print a JSON object describing the download (formats, output template, ...) after it
+    #[arg(long)]
+    print_json: bool,
+
+    /// Search engine to use when the input isn't an URL (e.g. ytsearch, ytsearch10, scsearch)
+    #[arg(long, value_name = "ENGINE", value_parser = parse::search_engine)]
+    search: Option<String>,
+
+    /// Edit the extra yt-dlp arguments before the download
+    #[arg(long)]
+    edit_extras: bool,
+
+    /// Don't overwrite post-processed files (implied unless overwriting the existing files)
+    #[arg(long)]
+    no_post_overwrites: bool,
+
+    /// Overwrite the existing files without asking
+    #[arg(long, conflicts_with_all = ["no_overwrites", "rename_on_conflict"])]
+    force_overwrites: bool,
+
+    /// Never overwrite the existing files, without asking (default when not asked)
+    #[arg(long)]
+    no_overwrites: bool,
+
+    /// Rename the download when the output file already exists, instead of asking
+    #[arg(long)]
+    rename_on_conflict: bool,
+
+    /// yt-dlp output template of the file name, `%(title)s` being the title from the prompt
+    ///
+    /// For example `%(uploader)s - %(title)s.%(ext)s`, `%(title)s` being required. [default: %(title)s.%(ext)s]
+    #[arg(long, value_name = "TEMPLATE", value_parser = parse::title_template)]
+    title_template: Option<String>,
+
+    /// Download into `YYYY/MM/` directories based on the upload date
+    #[arg(long)]
+    date_buckets: bool,
+
+    /// Time to sleep between the yt-dlp retries (e.g. 3, linear=1::2, fragment:exp=1:20)
+    ///
+    /// Applies between the retries yt-dlp does on its own during the download, whose count is set
+    /// by passing `--retries` to yt-dlp in the extra arguments (e.g. `-- --retries 10`).
+    /// Unrelated to `--fetch-retries`, which md does itself when fetching the media info.
+    #[arg(long, value_name = "SPEC", value_parser = parse::retry_sleep)]
+    retry_sleep: Vec<String>,
+
+    /// Show the storyboard formats in the video format selector
+    #[arg(long)]
+    show_storyboards: bool,
+
+    /// Print the presets that would be offered for the media and exit
+    #[arg(long)]
+    print_available_presets: bool,
+
+    /// Write metadata (like the source URL) to the file's extended attributes
+    #[arg(long)]
+    xattrs: bool,
+
+    /// Only offer and select video formats with at least this framerate
+    ///
+    /// Formats with an unknown framerate are excluded, unless `--lenient` is set.
+    #[arg(long, value_name = "FPS")]
+    min_fps: Option<u32>,
+
+    /// Download every video of a playlist instead of only the one in the url
+    #[arg(long)]
+    playlist: bool,
+
+    /// Write the playlist thumbnail, description and info json (requires --playlist)
+    #[arg(long, overrides_with = "no_write_playlist_metafiles")]
+    write_playlist_metafiles: bool,
+
+    /// Don't write the playlist thumbnail, description and info json
+    #[arg(long, overrides_with = "write_playlist_metafiles")]
+    no_write_playlist_metafiles: bool,
+
+    /// Sort order of the formats, like yt-dlp `-S` (e.g. res,fps,vcodec:av01)
+    #[arg(long, value_name = "SPEC", value_parser = parse::format_sort)]
+    format_sort: Option<String>,
+
+    /// Always fetch the media info instead of using the cached one
+    #[arg(long)]
+    no_cache: bool,
+
+    /// How long the fetched media info is cached, in seconds
+    #[arg(long, value_name = "SECONDS", default_value_t = 600)]
+    cache_ttl: u64,
+
+    /// Path of the yt-dlp binary
+    #[arg(
+        long = "yt-dlp",
+        value_name = "PATH",
+        env = "YT_DLP_BINARY",
+        default_value = "yt-dlp"
+    )]
+    yt_dlp: PathBuf,
+
+    /// Never prompt, use the flags, config and defaults instead (requires --preset)
+    ///
+    /// The title is the media title and subtitles are only embedded with --sub-langs.
+    #[arg(long, requires = "preset", conflicts_with = "edit_extras")]
+    non_interactive: bool,
+
+    /// Audio format to extract into with the best-audio preset
+    #[arg(long, value_enum, value_name = "FORMAT")]
+    audio_format: Option<AudioFormat>,
+
+    /// Quality of the extracted audio with the best-audio preset, from 0 (best) to 10 (worst)
+    ///
+    /// Asked for music when not given, yt-dlp defaults to 5 otherwise.
+    #[arg(long, value_name = "QUALITY", value_parser = clap::value_parser!(u8).range(0..=10))]
+    audio_quality: Option<u8>,
+
+    /// Reencode the video into this format after the download, not with the best-audio preset
+    #[arg(long, value_enum, value_name = "FORMAT")]
+    recode_video: Option<VideoFormat>,
+
+    /// Print the download command as a shell one-liner instead of running it
+    ///
+    /// The temporary info json it loads is kept, so the command can be run as-is.
+    #[arg(long, conflicts_with_all = ["print_json_after", "print_json"])]
+    dry_run: bool,
+
+    /// Directory where to keep a copy of the info json of the media
+    #[arg(long, value_name = "DIR")]
+    keep_info_json: Option<PathBuf>,
+
+    /// Show the DRM protected formats in the format selectors, they usually fail to download
+    #[arg(long)]
+    allow_drm: bool,
+
+    /// Maximum video height with the best and best-video presets (720, 1080, 1440 or 2160)
+    #[arg(long, value_name = "HEIGHT", value_parser = parse::quality)]
+    quality: Option<u32>,
+
+    /// SponsorBlock categories to remove, without asking (e.g. sponsor,intro)
+    #[arg(long, value_name = "CATEGORIES", value_parser = parse::sponsorblock)]
+    sponsorblock: Option<String>,
+
+    /// What to do with the SponsorBlock segments, without asking
+    #[arg(long, value_enum, value_name = "MODE")]
+    sponsorblock_mode: Option<SponsorblockMode>,
+
+    /// SponsorBlock API to use instead of the public one (e.g. https://sponsor.example.org)
+    #[arg(long, value_name = "URL", value_parser = parse::http_url)]
+    sponsorblock_api: Option<String>,
+
+    /// Only download a section of the media (e.g. 10:00-20:00, 1:02:03-inf)
+    ///
+    /// Asked for media longer than 30 minutes when not given. Cutting reencodes the media.
+    #[arg(long, value_name = "START-END", value_parser = parse::section)]
+    section: Option<parse::Section>,
+
+    /// Don't download files larger than this (e.g. 500MB, 1.5GiB)
+    ///
+    /// Also hides the larger formats from the format selectors.
+    #[arg(long, value_name = "SIZE", value_parser = parse::filesize)]
+    max_filesize: Option<u64>,
+
+    /// Print the formats of the media and exit
+    #[arg(long)]
+    list_formats: bool,
+
+    /// Download live streams without asking, the download lasts until the stream ends
+    #[arg(long)]
+    live: bool,
+
+    /// Download age restricted media (18+) without asking
+    #[arg(long)]
+    allow_age_restricted: bool,
+
+    /// Retries of the media info fetch after a network error, with an exponential backoff
+    ///
+    /// Unlike yt-dlp `--retries`, which applies to the download itself.
+    #[arg(long, value_name = "N", default_value_t = 3)]
+    fetch_retries: u32,
+
+    /// Record the downloaded videos in this file and skip the ones already in it
+    #[arg(long, value_name = "FILE")]
+    archive: Option<PathBuf>,
+
+    /// Stop at the first video of the playlist already in the archive (requires --archive)
+    #[arg(long, requires = "archive")]
+    break_on_existing: bool,
+
+    /// Only download the playlist videos uploaded since the last incremental run (requires --playlist)
+    ///
+    /// The first run downloads every video, the next ones pass `--dateafter` to yt-dlp.
+    #[arg(long, requires = "playlist")]
+    incremental: bool,
+
+    /// Resume the urls of the last batch that weren't downloaded, the failed ones included
+    ///
+    /// The queue of a batch of urls is saved after each url, and cleared once all were processed.
+    #[arg(long, conflicts_with_all = ["urls", "load_info_json"])]
+    resume_queue: bool,
+
+    /// Stop after this many downloads, across all the urls and playlist videos
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u32).range(1..))]
+    max_downloads: Option<u32>,
+
+    /// Split the media into one file per chapter, without asking
+    #[arg(long)]
+    split_chapters: bool,
+
+    /// Write the thumbnail to a file next to the media, without asking
+    #[arg(long)]
+    write_thumbnail: bool,
+
+    /// Write the description to a `.description` file next to the media, without asking
+    #[arg(long)]
+    write_description: bool,
+
+    /// Video codec to prefer with the best and best-video presets, without asking
+    #[arg(long, value_enum, value_name = "CODEC")]
+    prefer_codec: Option<Codec>,
+
+    /// Subtitle languages to embed, without asking (e.g. en,fr or en.* for all the variants)
+    #[arg(long, value_name = "LANGS", value_delimiter = ',')]
+    sub_langs: Vec<String>,
+
+    /// Also offer the automatic captions as subtitles
+    #[arg(long)]
+    auto_subs: bool,
+
+    /// Embed the metadata in the file, and the uploader as the artist of music
+    ///
+    /// On by default for music with the best-audio preset. The flags take precedence over
+    /// the `embed_metadata` config, which takes precedence over this default.
+    #[arg(long, overrides_with = "no_embed_metadata")]
+    embed_metadata: bool,
+
+    /// Don't embed the metadata in the file
+    #[arg(long, overrides_with = "embed_metadata")]
+    no_embed_metadata: bool,
+
+    /// Print the completions script for the shell and exit
+    #[arg(long, value_name = "SHELL", hide = true, exclusive = true)]
+    generate_completions: Option<clap_complete::Shell>,
+
+    /// Use this info json instead of fetching the metadata of an url
+    #[arg(long, value_name = "PATH", conflicts_with_all = ["urls", "search", "dump_json"])]
+    load_info_json: Option<PathBuf>,
+
+    /// Urls of the media to download, one after the other
+    #[arg(
+        value_name = "URL",
+        required_unless_present_any = ["load_info_json", "resume_queue", "config_dump"]
+    )]
+    urls: Vec<String>,
 
     /// Extra arguments to pass to yt-dlp
     #[arg(last = true)]
     extras: Vec<String>,
 }
 
-#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum, Deserialize)]
+#[serde(rename_all = "kebab-case")]
 enum Preset {
     /// Manual format to use
     #[value(skip)]
@@ -51,344 +414,3153 @@ enum Preset {
     BestVideo,
 }
 
-fn main() -> Result<(), anyhow::Error> {
-    let args = Args::parse();
+#[derive(Debug, Copy, Clone, PartialEq, Eq, ValueEnum)]
+enum AudioFormat {
+    Mp3,
+    Aac,
+    Flac,
+    Opus,
+    M4a,
+    Vorbis,
+    Wav,
+    Alac,
+    Best,
+}
 
-    let tempdir = std::mem::ManuallyDrop::new(
-        TempDir::new().context("couldn't create the temporary directory")?,
-    );
+#[derive(Debug, Copy, Clone, PartialEq, Eq, ValueEnum)]
+enum VideoFormat {
+    Avi,
+    Flv,
+    Gif,
+    Mkv,
+    Mov,
+    Mp4,
+    Webm,
+}
 
-    let mut command = Command::new("yt-dlp");
+#[derive(Debug, Copy, Clone, PartialEq, Eq, ValueEnum)]
+enum After {
+    /// Play the file with the default application
+    Play,
+    /// Open the directory of the file in the file manager
+    Open,
+    /// Do nothing
+    None,
+}
 
-    if args.quiet {
-        command.arg("--quiet");
+impl Display for After {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            After::Play => write!(f, "play the file"),
+            After::Open => write!(f, "open the directory"),
+            After::None => write!(f, "nothing"),
+        }
     }
+}
 
-    command
-        .arg("--write-info-json")
-        .arg("--skip-download")
-        .arg("--no-playlist")
-        .arg("-P")
-        .arg(tempdir.path())
-        .arg(&args.url)
-        .args(&args.extras);
+#[derive(Debug, Copy, Clone, PartialEq, Eq, ValueEnum)]
+enum SponsorblockMode {
+    /// Mark the segments as chapters
+    Mark,
+    /// Remove the segments, which reencodes the video
+    Remove,
+}
 
-    if args.verbose > 0 {
-        println!(" -> executing: {:?}", command);
+#[derive(Debug, Copy, Clone, PartialEq, Eq, ValueEnum, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+enum Codec {
+    Av01,
+    Vp9,
+    H264,
+}
+
+impl Codec {
+    /// Prefixes of the `vcodec` of the formats using this codec
+    fn prefixes(self) -> &'static [&'static str] {
+        match self {
+            Codec::Av01 => &["av01"],
+            Codec::Vp9 => &["vp9", "vp09"],
+            Codec::H264 => &["avc1", "h264"],
+        }
     }
 
-    let status = command.status()?;
+    fn matches(self, format: &infojson::Format) -> bool {
+        format
+            .vcodec
+            .as_deref()
+            .is_some_and(|vcodec| self.prefixes().iter().any(|p| vcodec.starts_with(p)))
+    }
 
-    if !status.success() {
-        bail!("yt-dlp error: {:?}", command);
+    /// yt-dlp format filter selecting this codec
+    fn filter(self) -> String {
+        format!("[vcodec~='^({})']", self.prefixes().join("|"))
     }
+}
 
-    let info_json_entry = std::fs::read_dir(tempdir.path())
-        .with_context(|| tempdir.path().display().to_string())?
-        .find_map(|entry| {
-            if let Ok(entry) = entry {
-                if entry.file_type().ok().map_or(false, |ft| ft.is_file()) {
-                    Some(entry)
-                } else {
-                    None
-                }
-            } else {
-                None
-            }
-        })
-        .context("directory empty")?;
-
-    let info_json =
-        BufReader::new(File::open(info_json_entry.path()).with_context(|| {
-            format!("unable to open file: {}", info_json_entry.path().display())
-        })?);
-    let info_json: infojson::InfoJson = serde_json::from_reader(info_json).with_context(|| {
-        format!(
-            "unable to read the info_json file: {}",
-            info_json_entry.path().display()
-        )
-    })?;
+#[derive(Debug, Copy, Clone, PartialEq, Eq, ValueEnum, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+enum Container {
+    Mp4,
+    Webm,
+    Mkv,
+}
 
-    let mut formats: Vec<Cow<str>> = Vec::new();
+impl Container {
+    /// Codec of the formats that doesn't go well into this container, if any
+    fn incompatible_codec(self, formats: &[&infojson::Format]) -> Option<String> {
+        let (vcodecs, acodecs): (&[&str], &[&str]) = match self {
+            Container::Mp4 => (&["vp8"], &["opus", "vorbis"]),
+            Container::Webm => (&["avc1", "h264", "hev1", "hvc1"], &["mp4a", "aac"]),
+            Container::Mkv => return None,
+        };
+        let is_any = |codec: &Option<String>, prefixes: &[&str]| {
+            codec
+                .as_ref()
+                .filter(|codec| prefixes.iter().any(|prefix| codec.starts_with(prefix)))
+                .cloned()
+        };
 
-    let is_music = info_json.categories.as_ref().map_or(false, |categories| {
-        categories
+        formats
             .iter()
-            .any(|cat| cat.eq_ignore_ascii_case("music"))
-    });
+            .find_map(|f| is_any(&f.vcodec, vcodecs).or_else(|| is_any(&f.acodec, acodecs)))
+    }
 
-    let has_some_video_only_format = info_json
-        .formats
-        .iter()
-        .any(|f| f.vcodec.is_some() && f.acodec.is_none());
-    let has_some_audio_only_format = info_json
-        .formats
-        .iter()
-        .any(|f| f.vcodec.is_none() && f.acodec.is_some());
+    /// Video and audio extensions that go into this container without remuxing
+    ///
+    /// `None` when any video and audio can be merged into it as-is.
+    fn exts(self) -> Option<(&'static str, &'static str)> {
+        match self {
+            Container::Mp4 => Some(("mp4", "m4a")),
+            Container::Webm => Some(("webm", "webm")),
+            Container::Mkv => None,
+        }
+    }
+}
 
-    let preset = if let Some(preset) = args.preset {
-        preset
-    } else {
-        let presets = if has_some_audio_only_format && has_some_video_only_format {
-            &[
-                Preset::Manual,
-                Preset::Custom,
-                Preset::Best,
-                Preset::BestAudio,
-                Preset::BestVideo,
-            ] as &[_]
-        } else if has_some_audio_only_format {
-            &[
-                Preset::Manual,
-                Preset::Custom,
-                Preset::Best,
-                Preset::BestAudio,
-            ] as &[_]
-        } else if has_some_video_only_format {
-            &[
-                Preset::Manual,
-                Preset::Custom,
-                Preset::Best,
-                Preset::BestVideo,
-            ] as &[_]
-        } else {
-            &[Preset::Manual, Preset::Custom, Preset::Best] as &[_]
-        };
+impl Preset {
+    fn description(self) -> &'static str {
+        match self {
+            Preset::Manual => "Manual format to use",
+            Preset::Custom => "Select a custom format",
+            Preset::Best => "Use the \"best\" format",
+            Preset::BestAudio => "Best audio-only format",
+            Preset::BestVideo => "Best video-only format",
+        }
+    }
+}
 
-        match prep_select_preset(presets.iter().copied())
-            .with_starting_cursor(if is_music { 3 } else { 2 })
-            .prompt()
-        {
-            Ok(PresetDisplay(preset)) => preset,
-            Err(_) => return Ok(()),
+fn main() -> Result<(), anyhow::Error> {
+    let matches = Args::command().get_matches();
+    let mut args = Args::from_arg_matches(&matches).unwrap_or_else(|err| err.exit());
+
+    if let Some(shell) = args.generate_completions {
+        let mut command = Args::command();
+        let name = command.get_name().to_string();
+        clap_complete::generate(shell, &mut command, name, &mut std::io::stdout());
+        return Ok(());
+    }
+
+    let config_path = args.config.clone().or_else(config::Config::default_path);
+    let mut config = match &config_path {
+        Some(path) => config::Config::load(path)?,
+        None => config::Config::default(),
+    };
+
+    if args.limit_rate.is_none() {
+        if let Some(rate) = config.rate_limit_at(Local::now().time()) {
+            if !args.config_dump {
+                eprintln!(
+                    " -> note: limiting the download rate to {rate}, per the config rate_limits"
+                );
+            }
+            args.limit_rate = Some(rate.to_string());
         }
+    }
+
+    let profile = match &args.profile {
+        Some(name) => Some(config.apply_profile(name)?),
+        None => None,
     };
+    if let Some(profile) = &profile {
+        // the flags take precedence over the profile
+        args.quality = args.quality.or(profile.quality);
+        args.prefer_codec = args.prefer_codec.or(profile.prefer_codec);
+        args.prefer_container = args.prefer_container.or(profile.prefer_container);
+        args.merge_output_format = args.merge_output_format.or(profile.merge_output_format);
+        if args.max_filesize.is_none() {
+            args.max_filesize = profile
+                .max_filesize
+                .as_deref()
+                .map(parse::filesize)
+                .transpose()
+                .map_err(anyhow::Error::msg)?;
+        }
+    }
 
-    match preset {
-        Preset::Custom => {
-            let video_format = match prep_select_video(info_json.formats.iter()).prompt() {
-                Ok(VideoFormatDisplay(format)) => format,
-                Err(_) => return Ok(()),
-            };
-            formats.push((&video_format.format_id).into());
-            if video_format.acodec.is_none() {
-                match prep_select_audio(info_json.formats.iter()).prompt() {
-                    Ok(AudioFormatDisplay(format)) => formats.push((&format.format_id).into()),
-                    Err(_) => return Ok(()),
+    if args.config_dump {
+        print!(
+            "{}",
+            config_dump(
+                &args,
+                &matches,
+                &config,
+                config_path.as_deref(),
+                profile.as_ref()
+            )
+        );
+        return Ok(());
+    }
+
+    if args.non_interactive && args.preset == Some(Preset::Custom) {
+        bail!("the custom preset needs the format prompts, use --preset best, best-audio or best-video with --non-interactive");
+    }
+
+    if args.audio_format.is_some() && args.preset.is_some_and(|p| p != Preset::BestAudio) {
+        bail!("--audio-format is only supported with the best-audio preset");
+    }
+
+    if args.audio_quality.is_some() && args.preset.is_some_and(|p| p != Preset::BestAudio) {
+        bail!("--audio-quality is only supported with the best-audio preset");
+    }
+
+    if args.recode_video.is_some() && args.preset == Some(Preset::BestAudio) {
+        bail!("--recode-video isn't supported with the best-audio preset, use --audio-format");
+    }
+
+    if !args.non_interactive && !std::io::stdin().is_terminal() {
+        eprintln!(" -> note: stdin is not a terminal, use --non-interactive to never prompt");
+    }
+
+    if let Some(cookies) = &args.cookies {
+        if !cookies.is_file() {
+            bail!("cookies file not found: {}", cookies.display());
+        }
+    }
+
+    if let Some(output_dir) = &args.output_dir {
+        if !output_dir.is_dir() {
+            if !args.non_interactive {
+                match Confirm::new(&format!(
+                    "Create the output directory {}?",
+                    output_dir.display()
+                ))
+                .with_default(true)
+                .prompt()
+                {
+                    Ok(true) => {}
+                    Ok(false) | Err(_) => return Ok(()),
                 }
             }
+            std::fs::create_dir_all(output_dir).with_context(|| {
+                format!(
+                    "unable to create the output directory: {}",
+                    output_dir.display()
+                )
+            })?;
         }
-        Preset::BestAudio => formats.push("bestaudio".into()),
-        Preset::BestVideo => formats.push("bestvideo".into()),
-        Preset::Best => formats.push("bv*+ba/b".into()),
-        Preset::Manual => match Text::new("Format?").prompt() {
-            Ok(format) => formats.push(format.into()),
-            Err(_) => return Ok(()),
-        },
     }
 
-    let output_template = {
-        let title = match Text::new("Title?")
-            .with_initial_value(&info_json.title)
-            .prompt()
-        {
-            Ok(title) => title,
-            Err(_) => return Ok(()),
-        };
+    if !use_color(&args) {
+        inquire::set_global_render_config(inquire::ui::RenderConfig::empty());
+    }
 
-        format!("{title}.%(ext)s")
-    };
+    check_yt_dlp(&args)?;
 
-    let embed_thumbnail = {
-        match Confirm::new("Embed thumbnail?")
-            .with_default(
-                matches!(preset, Preset::BestAudio | Preset::BestVideo)
-                    && matches!(Path::new("/bin/mutagen-inspect").try_exists(), Ok(true)),
-            )
-            .prompt()
-        {
-            Ok(confirm) => confirm,
+    let cookies_from_browser = match &args.cookies_from_browser {
+        Some(spec) if args.non_interactive => Some(spec.clone()),
+        Some(spec) => match select_browser_profile(spec) {
+            Ok(spec) => Some(spec),
             Err(_) => return Ok(()),
-        }
+        },
+        None => None,
     };
 
-    let embed_chapters = if !matches!(preset, Preset::BestAudio) {
-        match Confirm::new("Embed chapters?")
-            .with_default(matches!(preset, Preset::Best | Preset::BestVideo))
-            .prompt()
-        {
-            Ok(confirm) => confirm,
-            Err(_) => return Ok(()),
+    let mut downloaded = Vec::new();
+
+    if let Some(path) = &args.load_info_json {
+        download_info_json(
+            &args,
+            &config,
+            cookies_from_browser.as_deref(),
+            path,
+            &mut downloaded,
+        )?;
+        return Ok(());
+    }
+
+    if let [url] = &args.urls[..] {
+        download_url(
+            &args,
+            &config,
+            cookies_from_browser.as_deref(),
+            url,
+            &mut downloaded,
+        )?;
+        return Ok(());
+    }
+
+    let mut queue = if args.resume_queue {
+        match queue::Queue::load()? {
+            Some(queue)
+                if queue
+                    .entries
+                    .iter()
+                    .any(|(status, _)| *status != queue::Status::Done) =>
+            {
+                queue
+            }
+            _ => bail!("no queue to resume, the last batch was fully processed"),
         }
     } else {
-        false
+        if let Ok(Some(previous)) = queue::Queue::load() {
+            let remaining = previous
+                .entries
+                .iter()
+                .filter(|(status, _)| *status != queue::Status::Done)
+                .count();
+            if remaining > 0 {
+                eprintln!(" -> warning: replacing the queue of the last batch, with {remaining} url(s) remaining");
+            }
+        }
+        queue::Queue::new(&args.urls)
+    };
+    let save_queue = |queue: &queue::Queue| {
+        if let Err(err) = queue.save() {
+            eprintln!(" -> warning: unable to save the queue: {err}");
+        }
     };
+    save_queue(&queue);
 
-    let embed_subtitles = if let Some(subtitles) = &info_json.subtitles {
-        if !matches!(preset, Preset::BestAudio) && !subtitles.is_empty() {
-            let subs = subtitles.iter().flat_map(|(n, s)| match s {
-                infojson::Subtitles::Normal(s) => Some((n.as_ref(), s.as_slice())),
-                _ => None,
-            });
-            match prep_multiselect_subtitle(subs).prompt() {
-                Ok(subs) if !subs.is_empty() => Some(subs),
-                Ok(_) => None,
-                Err(_) => return Ok(()),
+    let urls: Vec<(usize, String)> = queue
+        .entries
+        .iter()
+        .enumerate()
+        .filter(|(_, (status, _))| *status != queue::Status::Done)
+        .map(|(i, (_, url))| (i, url.clone()))
+        .collect();
+
+    let mut succeeded = 0;
+    let mut failures = Vec::new();
+    let mut cancelled = None;
+    let mut skipped: &[(usize, String)] = &[];
+    for (n, (i, url)) in urls.iter().enumerate() {
+        let result = download_url(
+            &args,
+            &config,
+            cookies_from_browser.as_deref(),
+            url,
+            &mut downloaded,
+        );
+        queue.entries[*i].0 = match result {
+            Ok(true) => queue::Status::Done,
+            Ok(false) => queue::Status::Pending,
+            Err(_) => queue::Status::Failed,
+        };
+        save_queue(&queue);
+
+        match result {
+            Ok(true) => {
+                succeeded += 1;
+                if args
+                    .max_downloads
+                    .is_some_and(|max| downloaded.len() >= max as usize)
+                {
+                    skipped = &urls[n + 1..];
+                    break;
+                }
+            }
+            Ok(false) => {
+                cancelled = Some(url);
+                skipped = &urls[n + 1..];
+                break;
+            }
+            Err(err) => {
+                eprintln!(" -> error: {url}: {err:#}");
+                failures.push(url);
             }
-        } else {
-            None
         }
-    } else {
-        None
-    };
+    }
 
-    let sponsorblock_remove = if info_json.extractor_key.eq_ignore_ascii_case("youtube")
-        && !matches!(preset, Preset::BestAudio)
-    {
-        match Confirm::new("Remove sponsor blocks?")
-            .with_default(false)
-            .with_help_message("warn: will reencode")
-            .prompt()
-        {
-            Ok(confirm) => confirm,
-            Err(_) => return Ok(()),
+    eprint!(
+        " -> summary: {succeeded} url(s) succeeded, {} failed",
+        failures.len()
+    );
+    if cancelled.is_some() {
+        eprint!(", 1 cancelled");
+    }
+    if !skipped.is_empty() {
+        eprint!(", {} skipped", skipped.len());
+    }
+    eprintln!();
+    for url in &failures {
+        eprintln!("     - {url}");
+    }
+    if let Some(url) = cancelled {
+        eprintln!("     - {url} (cancelled)");
+    }
+    for (_, url) in skipped {
+        eprintln!("     - {url} (skipped)");
+    }
+
+    if cancelled.is_none() && skipped.is_empty() {
+        if let Err(err) = queue::Queue::clear() {
+            eprintln!(" -> warning: unable to clear the queue: {err}");
         }
     } else {
-        false
+        eprintln!(" -> note: the remaining urls can be resumed with --resume-queue");
+    }
+
+    if !failures.is_empty() {
+        bail!("{} of the {} urls failed", failures.len(), urls.len());
+    }
+
+    Ok(())
+}
+
+/// Fetch the metadata of the url and download its videos
+///
+/// Returns `false` when cancelled, to stop processing the next urls.
+fn download_url(
+    args: &Args,
+    config: &config::Config,
+    cookies_from_browser: Option<&str>,
+    url: &str,
+    downloaded: &mut Vec<DownloadedVideo>,
+) -> Result<bool, anyhow::Error> {
+    let url: Cow<str> = match &args.search {
+        Some(engine) if !looks_like_url(url) => {
+            match search(args, engine, cookies_from_browser, url)? {
+                Some(url) => url.into(),
+                None => return Ok(false),
+            }
+        }
+        _ => url.into(),
+    };
+
+    if args.dump_json {
+        let mut command = Command::new(&args.yt_dlp);
+
+        add_cookies(&mut command, args, cookies_from_browser);
+
+        command.arg("--dump-single-json");
+
+        if !args.playlist {
+            command.arg("--no-playlist");
+        }
+
+        command.arg(&*url).args(&args.extras);
+
+        if let Some(hook) = &args.command_hook {
+            run_command_hook(hook, &mut command)?;
+        }
+
+        if args.verbose > 0 {
+            eprintln!(" -> executing: {:?}", command);
+        }
+
+        let status = status_with_retries(
+            &mut command,
+            args.fetch_retries,
+            args.verbose > 0,
+            (!args.quiet && std::io::stderr().is_terminal()).then_some("Fetching metadata…"),
+            false,
+        )?;
+
+        if !status.success() {
+            bail!("yt-dlp error: {:?}", command);
+        }
+
+        return Ok(true);
+    }
+
+    let mut archive = match &args.archive {
+        Some(path) => read_archive(path)?,
+        None => HashSet::new(),
+    };
+
+    let incremental = args.incremental.then(|| incremental::State::load(&url));
+    if let Some(state) = &incremental {
+        match &state.date {
+            Some(date) if args.verbose > 0 => {
+                eprintln!(" -> note: only fetching the videos uploaded since {date}")
+            }
+            Some(_) => {}
+            None => {
+                eprintln!(" -> note: first incremental run of the url, downloading every video")
+            }
+        }
+        // the videos of the last date are fetched again by `--dateafter`
+        archive.extend(state.entries.iter().cloned());
+    }
+
+    if let Some(id) = youtube_id(&url) {
+        if archive.contains(&format!("youtube {id}")) {
+            eprintln!(" -> note: {id} is already in the archive, skipping");
+            return Ok(true);
+        }
+    }
+
+    let tempdir = TempDir::new().context("couldn't create the temporary directory")?;
+
+    // the cached info json would ignore `--dateafter`
+    let cache_key = cache_key(args, cookies_from_browser, &url);
+    let cached = !args.no_cache
+        && !args.incremental
+        && cache::restore(
+            &cache_key,
+            Duration::from_secs(args.cache_ttl),
+            tempdir.path(),
+        );
+
+    if cached {
+        if args.verbose > 0 {
+            eprintln!(" -> note: using the cached info json");
+        }
+    } else {
+        let mut command = Command::new(&args.yt_dlp);
+
+        if args.quiet {
+            command.arg("--quiet");
+        }
+
+        add_cookies(&mut command, args, cookies_from_browser);
+
+        command.arg("--write-info-json").arg("--skip-download");
+
+        if !args.playlist {
+            command.arg("--no-playlist");
+        }
+
+        if let Some(date) = incremental.as_ref().and_then(|state| state.date.as_ref()) {
+            command.arg("--dateafter").arg(date);
+        }
+
+        command
+            .arg("-P")
+            .arg(tempdir.path())
+            .arg(&*url)
+            .args(&args.extras);
+
+        if let Some(hook) = &args.command_hook {
+            run_command_hook(hook, &mut command)?;
+        }
+
+        if args.verbose > 0 {
+            eprintln!(" -> executing: {:?}", command);
+        }
+
+        let status = status_with_retries(
+            &mut command,
+            args.fetch_retries,
+            args.verbose > 0,
+            (!args.quiet && std::io::stderr().is_terminal()).then_some("Fetching metadata…"),
+            // keep stdout for the JSON output
+            args.print_json || args.print_json_after,
+        )?;
+
+        if !status.success() {
+            bail!("yt-dlp error: {:?}", command);
+        }
+    }
+
+    let videos = match read_info_jsons(tempdir.path(), args.verbose > 0) {
+        Ok(videos) => videos,
+        Err(err) if incremental.is_some() && err.is::<EmptyPlaylist>() => {
+            eprintln!(" -> note: no new video since the last incremental run");
+            return Ok(true);
+        }
+        Err(err) => return Err(err),
+    };
+
+    if !cached
+        && !args.no_cache
+        && !args.incremental
+        && !videos
+            .iter()
+            .any(|(_, info_json)| info_json.is_live == Some(true))
+    {
+        if let Err(err) = cache::store(&cache_key, tempdir.path()) {
+            eprintln!(" -> warning: unable to cache the info json: {err}");
+        }
+    }
+
+    let first = downloaded.len();
+    let completed = download_videos(
+        args,
+        config,
+        cookies_from_browser,
+        tempdir,
+        videos,
+        &archive,
+        downloaded,
+    )?;
+
+    if let Some(mut state) = incremental {
+        if completed && !args.dry_run {
+            for video in &downloaded[first..] {
+                state.record(&video.archive_entry, video.upload_date.as_deref());
+            }
+            if let Err(err) = state.save(&url) {
+                eprintln!(" -> warning: unable to save the incremental state: {err}");
+            }
+        }
+    }
+
+    Ok(completed)
+}
+
+/// Download the video of the info json, without fetching its metadata
+fn download_info_json(
+    args: &Args,
+    config: &config::Config,
+    cookies_from_browser: Option<&str>,
+    path: &Path,
+    downloaded: &mut Vec<DownloadedVideo>,
+) -> Result<bool, anyhow::Error> {
+    let info_json = BufReader::new(
+        File::open(path).with_context(|| format!("unable to open file: {}", path.display()))?,
+    );
+    let info_json: infojson::AnyInfoJson = serde_json::from_reader(info_json)
+        .with_context(|| format!("unable to read the info_json file: {}", path.display()))?;
+
+    let info_json = match info_json {
+        infojson::AnyInfoJson::Video(info_json) => info_json,
+        infojson::AnyInfoJson::Playlist(_) => {
+            bail!(
+                "the info json is a playlist, not a video: {}",
+                path.display()
+            )
+        }
+    };
+    if info_json.formats.is_empty() {
+        bail!("the info json has no formats: {}", path.display());
+    }
+
+    let archive = match &args.archive {
+        Some(path) => read_archive(path)?,
+        None => HashSet::new(),
+    };
+
+    // copied since the thumbnail selection patches it
+    let tempdir = TempDir::new().context("couldn't create the temporary directory")?;
+    let copy = tempdir
+        .path()
+        .join(path.file_name().unwrap_or("video.info.json".as_ref()));
+    std::fs::copy(path, &copy)
+        .with_context(|| format!("unable to copy the info json to: {}", copy.display()))?;
+
+    download_videos(
+        args,
+        config,
+        cookies_from_browser,
+        tempdir,
+        vec![(copy, info_json)],
+        &archive,
+        downloaded,
+    )
+}
+
+/// Download the videos not in the archive, in the playlist order
+///
+/// `downloaded` collects the downloads across all the urls, up to `--max-downloads`.
+/// Returns `false` when cancelled, to stop processing the next urls.
+fn download_videos(
+    args: &Args,
+    config: &config::Config,
+    cookies_from_browser: Option<&str>,
+    tempdir: TempDir,
+    mut videos: Vec<(PathBuf, Box<infojson::InfoJson>)>,
+    archive: &HashSet<String>,
+    downloaded: &mut Vec<DownloadedVideo>,
+) -> Result<bool, anyhow::Error> {
+    let in_archive = |info_json: &infojson::InfoJson| archive.contains(&archive_entry(info_json));
+
+    videos.sort_by_key(|(_, info_json)| info_json.playlist_index);
+
+    if args.break_on_existing {
+        if let Some(i) = videos
+            .iter()
+            .position(|(_, info_json)| in_archive(info_json))
+        {
+            eprintln!(
+                " -> note: {} is already in the archive, skipping it and the next {} video(s)",
+                videos[i].1.title,
+                videos.len() - i - 1
+            );
+            videos.truncate(i);
+        }
+    }
+
+    videos.retain(|(_, info_json)| {
+        let in_archive = in_archive(info_json);
+        if in_archive {
+            eprintln!(
+                " -> note: {} is already in the archive, skipping",
+                info_json.title
+            );
+        }
+        !in_archive
+    });
+
+    let same_preset = if videos.len() > 1
+        && args.preset.is_none()
+        && !args.simulate
+        && !args.print_available_presets
+        && !args.list_formats
+    {
+        match Confirm::new(&format!(
+            "Use the same preset for all {} videos?",
+            videos.len()
+        ))
+        .with_default(true)
+        .prompt()
+        {
+            Ok(confirm) => confirm,
+            Err(_) => return Ok(false),
+        }
+    } else {
+        true
+    };
+
+    let mut cancelled = false;
+    let mut preset = args.preset;
+    for (path, info_json) in &videos {
+        match download_video(
+            args,
+            config,
+            cookies_from_browser,
+            tempdir.path(),
+            path,
+            info_json,
+            preset,
+        )? {
+            Outcome::Downloaded(used) => {
+                if same_preset {
+                    preset = Some(used);
+                }
+                downloaded.push(DownloadedVideo {
+                    archive_entry: archive_entry(info_json),
+                    upload_date: info_json.upload_date.clone(),
+                });
+                if args.max_downloads == Some(downloaded.len() as u32) {
+                    eprintln!(
+                        " -> note: stopping after {} download(s), per --max-downloads",
+                        downloaded.len()
+                    );
+                    break;
+                }
+            }
+            Outcome::Skipped => {}
+            Outcome::Cancelled => {
+                cancelled = true;
+                break;
+            }
+        }
+    }
+
+    if let Some(keep_dir) = &args.keep_info_json {
+        std::fs::create_dir_all(keep_dir)
+            .with_context(|| format!("unable to create directory: {}", keep_dir.display()))?;
+
+        for (path, _) in &videos {
+            let Some(file_name) = path.file_name() else {
+                continue;
+            };
+            let kept = keep_dir.join(file_name);
+            std::fs::copy(path, &kept)
+                .with_context(|| format!("unable to copy the info json to: {}", kept.display()))?;
+            eprintln!(" -> note: info json kept in {}", kept.display());
+        }
+    }
+
+    if args.dry_run {
+        let tempdir = tempdir.into_path();
+        eprintln!(" -> note: keeping the info json in {}", tempdir.display());
+    }
+
+    Ok(!cancelled)
+}
+
+/// Read the `.info.json` files written by yt-dlp in the directory, skipping the playlists ones
+///
+/// Fails when there are no videos, with distinct errors when yt-dlp wrote nothing or no
+/// `.info.json` files.
+fn read_info_jsons(
+    dir: &Path,
+    verbose: bool,
+) -> Result<Vec<(PathBuf, Box<infojson::InfoJson>)>, anyhow::Error> {
+    let mut files = 0;
+    let mut playlists = 0;
+    let mut videos = Vec::new();
+
+    for entry in std::fs::read_dir(dir).with_context(|| dir.display().to_string())? {
+        let path = entry?.path();
+        files += 1;
+        if !path.is_file() || !path.to_string_lossy().ends_with(".info.json") {
+            continue;
+        }
+
+        let info_json = BufReader::new(
+            File::open(&path)
+                .with_context(|| format!("unable to open file: {}", path.display()))?,
+        );
+        let info_json: infojson::AnyInfoJson = serde_json::from_reader(info_json)
+            .with_context(|| format!("unable to read the info_json file: {}", path.display()))?;
+
+        match info_json {
+            infojson::AnyInfoJson::Video(info_json) => videos.push((path, info_json)),
+            infojson::AnyInfoJson::Playlist(playlist) => {
+                playlists += 1;
+                if verbose {
+                    eprintln!(
+                        " -> playlist: {}",
+                        playlist.title.as_deref().unwrap_or(&playlist.id)
+                    );
+                }
+            }
+        }
+    }
+
+    if files == 0 {
+        bail!("directory empty, yt-dlp didn't write the info json");
+    }
+    if videos.is_empty() && playlists > 0 {
+        return Err(EmptyPlaylist.into());
+    }
+    if videos.is_empty() {
+        bail!(
+            "no .info.json file among the {files} file(s) written by yt-dlp in {}",
+            dir.display()
+        );
+    }
+
+    Ok(videos)
+}
+
+/// Entry of the video in a yt-dlp download archive, `extractor id`
+fn archive_entry(info_json: &infojson::InfoJson) -> String {
+    format!(
+        "{} {}",
+        info_json.extractor_key.to_lowercase(),
+        info_json.id
+    )
+}
+
+/// Video downloaded, or that would have been with `--dry-run`
+struct DownloadedVideo {
+    /// Entry of the video in a download archive
+    archive_entry: String,
+    /// Upload date as `YYYYMMDD`
+    upload_date: Option<String>,
+}
+
+/// Error of a playlist without any video info json, all its videos being filtered out
+#[derive(Debug)]
+struct EmptyPlaylist;
+
+impl Display for EmptyPlaylist {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "the playlist has no videos")
+    }
+}
+
+impl std::error::Error for EmptyPlaylist {}
+
+/// Outcome of processing one video
+enum Outcome {
+    /// Downloaded with the given preset, or would have been with `--dry-run`
+    Downloaded(Preset),
+    /// Nothing to download, like in `--simulate` mode
+    Skipped,
+    /// Cancelled by the user
+    Cancelled,
+}
+
+/// Prompt for the choices about one video and download it
+fn download_video(
+    args: &Args,
+    config: &config::Config,
+    cookies_from_browser: Option<&str>,
+    tempdir: &Path,
+    info_json_path: &Path,
+    info_json: &infojson::InfoJson,
+    preset: Option<Preset>,
+) -> Result<Outcome, anyhow::Error> {
+    if args.simulate {
+        println!(" -> ok: {} ({})", info_json.title, info_json.webpage_url);
+        return Ok(Outcome::Skipped);
+    }
+
+    let color = use_color(args);
+    let mut formats: Vec<Cow<str>> = Vec::new();
+    let mut selected_formats: Vec<&infojson::Format> = Vec::new();
+
+    let is_music = info_json.categories.as_ref().is_some_and(|categories| {
+        categories
+            .iter()
+            .any(|cat| cat.eq_ignore_ascii_case("music"))
+    });
+
+    if args.verbose > 0 {
+        let without_id = info_json
+            .formats
+            .iter()
+            .filter(|f| f.format_id.trim().is_empty())
+            .count();
+        if without_id > 0 {
+            eprintln!(" -> warning: skipping {without_id} format(s) without a format_id");
+        }
+    }
+
+    if args.print_available_presets {
+        let rows: Vec<Vec<String>> = available_presets(&info_json.formats)
+            .iter()
+            .map(|&preset| {
+                vec![
+                    PresetDisplay(preset).to_string(),
+                    preset.description().to_string(),
+                ]
+            })
+            .collect();
+        print_table(&["PRESET", "DESCRIPTION"], &rows);
+
+        return Ok(Outcome::Skipped);
+    }
+
+    if args.list_formats {
+        print_formats(&info_json.formats);
+        return Ok(Outcome::Skipped);
+    }
+
+    eprint!(
+        "{}",
+        MediaSummary {
+            info_json,
+            verbose: args.verbose > 0,
+        }
+    );
+
+    if info_json.is_live == Some(true) && !args.live {
+        eprintln!(" -> warning: this is a live stream, the download won't stop until it ends");
+
+        if args.non_interactive {
+            bail!("refusing to download a live stream, pass --live to download it");
+        }
+        match Confirm::new("Download the live stream anyway?")
+            .with_default(false)
+            .prompt()
+        {
+            Ok(true) => {}
+            Ok(false) | Err(_) => return Ok(Outcome::Cancelled),
+        }
+    }
+
+    if info_json.age_limit.is_some_and(|age| age >= 18) && !args.allow_age_restricted {
+        if args.non_interactive {
+            bail!("refusing to download age restricted media, pass --allow-age-restricted to download it");
+        }
+        match Confirm::new("Download the age restricted media anyway?")
+            .with_default(false)
+            .prompt()
+        {
+            Ok(true) => {}
+            Ok(false) | Err(_) => return Ok(Outcome::Cancelled),
+        }
+    }
+
+    let preset = if let Some(preset) = preset {
+        preset
+    } else {
+        let presets = available_presets(&info_json.formats);
+
+        match prep_select_preset(presets.iter().copied())
+            .with_starting_cursor(
+                config
+                    .preset_for(&info_json.extractor_key)
+                    .and_then(|default| presets.iter().position(|&p| p == default))
+                    .unwrap_or(if is_music { 3 } else { 2 }),
+            )
+            .prompt()
+        {
+            Ok(PresetDisplay(preset)) => preset,
+            Err(_) => return Ok(Outcome::Cancelled),
+        }
+    };
+
+    if args.audio_format.is_some() && preset != Preset::BestAudio {
+        bail!("--audio-format is only supported with the best-audio preset");
+    }
+
+    if args.audio_quality.is_some() && preset != Preset::BestAudio {
+        bail!("--audio-quality is only supported with the best-audio preset");
+    }
+
+    if args.recode_video.is_some() && preset == Preset::BestAudio {
+        bail!("--recode-video isn't supported with the best-audio preset, use --audio-format");
+    }
+
+    let audio_quality = if args.audio_quality.is_some()
+        || args.non_interactive
+        || preset != Preset::BestAudio
+        || !is_music
+    {
+        args.audio_quality
+    } else {
+        let options = (0..=10)
+            .map(|quality| match quality {
+                0 => "0 (best)".to_string(),
+                10 => "10 (worst)".to_string(),
+                quality => quality.to_string(),
+            })
+            .collect();
+        match Select::new("Audio quality?", options).raw_prompt() {
+            Ok(choice) => Some(choice.index as u8),
+            Err(_) => return Ok(Outcome::Cancelled),
+        }
+    };
+
+    match preset {
+        Preset::Custom => {
+            if args.compare || args.verbose > 1 {
+                print_format_comparison(&info_json.formats);
+            }
+
+            let video_filters = video_filters(args, &info_json.formats);
+            let Some(video_formats) =
+                filter_formats("video", &info_json.formats, &video_filters, args.lenient)?
+            else {
+                return Ok(Outcome::Cancelled);
+            };
+
+            let video_format =
+                match prep_select_video(video_formats.into_iter(), args.default_height, color)
+                    .prompt()
+                {
+                    Ok(VideoFormatDisplay(format, _)) => format,
+                    Err(_) => return Ok(Outcome::Cancelled),
+                };
+            formats.push((&video_format.format_id).into());
+            selected_formats.push(video_format);
+
+            // formats with unknown codecs are assumed to already contain the audio
+            if video_format.acodec.is_none() && video_format.vcodec.is_some() {
+                let mut audio_filters = vec![
+                    FormatFilter::new("audio codec", |f| {
+                        f.acodec.is_some() /*&& f.vcodec.is_none()*/
+                    }),
+                    FormatFilter::new("storyboard", |f| !f.is_storyboard()),
+                ];
+                if !args.allow_drm {
+                    audio_filters.push(FormatFilter::new("DRM protection", |f| {
+                        f.has_drm != Some(true)
+                    }));
+                }
+                if let Some(max_filesize) = args.max_filesize {
+                    audio_filters.push(FormatFilter::new("--max-filesize", move |f| {
+                        f.size().is_none_or(|size| size <= max_filesize)
+                    }));
+                }
+                let Some(audio_formats) =
+                    filter_formats("audio", &info_json.formats, &audio_filters, args.lenient)?
+                else {
+                    return Ok(Outcome::Cancelled);
+                };
+
+                match prep_select_audio(audio_formats.into_iter(), color).prompt() {
+                    Ok(AudioFormatDisplay(format, _)) => {
+                        formats.push((&format.format_id).into());
+                        selected_formats.push(format);
+                    }
+                    Err(_) => return Ok(Outcome::Cancelled),
+                }
+            }
+        }
+        Preset::BestAudio | Preset::BestVideo | Preset::Best => {
+            let mut video_filter = String::new();
+            if let Some(min_fps) = args.min_fps {
+                let op = if args.lenient { ">=?" } else { ">=" };
+                video_filter.push_str(&format!("[fps{op}{min_fps}]"));
+            }
+            if let Some(quality) = args.quality {
+                let op = if args.lenient { "<=?" } else { "<=" };
+                video_filter.push_str(&format!("[height{op}{quality}]"));
+            }
+
+            let selector = match preset {
+                Preset::BestAudio => "bestaudio".to_string(),
+                Preset::BestVideo => format!("bestvideo{video_filter}"),
+                _ => format!("bv*{video_filter}+ba/b{video_filter}"),
+            };
+
+            let mut alternatives = Vec::new();
+
+            let available_codecs: Vec<Codec> = Codec::value_variants()
+                .iter()
+                .copied()
+                .filter(|codec| info_json.formats.iter().any(|f| codec.matches(f)))
+                .collect();
+            let prefer_codec = if matches!(preset, Preset::BestAudio) {
+                None
+            } else if let Some(codec) = args.prefer_codec {
+                if !available_codecs.contains(&codec) {
+                    eprintln!(" -> note: no formats with the preferred codec");
+                }
+                Some(codec)
+            } else if args.non_interactive || available_codecs.len() < 2 {
+                None
+            } else {
+                let mut options = vec!["no preference".to_string()];
+                options.extend(available_codecs.iter().map(|&codec| value_name(codec)));
+                match Select::new("Which video codec do you prefer?", options).raw_prompt() {
+                    Ok(choice) if choice.index > 0 => Some(available_codecs[choice.index - 1]),
+                    Ok(_) => None,
+                    Err(_) => return Ok(Outcome::Cancelled),
+                }
+            };
+            if let Some(codec) = prefer_codec {
+                let video_filter = format!("{}{video_filter}", codec.filter());
+                alternatives.push(match preset {
+                    Preset::BestVideo => format!("bestvideo{video_filter}"),
+                    _ => format!("bv*{video_filter}+ba"),
+                });
+            }
+
+            if let Some(exts) = args.prefer_container.and_then(Container::exts) {
+                match prefer_container_selector(preset, exts, &video_filter, &info_json.formats) {
+                    Some(preferred) => alternatives.push(preferred),
+                    None => {
+                        eprintln!(" -> note: no formats compatible with the preferred container")
+                    }
+                }
+            }
+
+            alternatives.push(selector);
+            formats.push(alternatives.join("/").into());
+        }
+        Preset::Manual => match Text::new("Format?").prompt() {
+            Ok(format) => formats.push(format.into()),
+            Err(_) => return Ok(Outcome::Cancelled),
+        },
+    }
+
+    if selected_formats.iter().any(|f| f.fragments.is_some()) {
+        eprintln!(
+            " -> note: the selected format is fragmented, unavailable fragments will {}",
+            if args.abort_on_unavailable_fragments {
+                "abort the download"
+            } else {
+                "be skipped"
+            }
+        );
+    }
+
+    let output_template = {
+        let title = if args.non_interactive {
+            info_json.title.clone()
+        } else {
+            let mut history = history::TitleHistory::load();
+            let initial = info_json
+                .channel_id
+                .as_deref()
+                .and_then(|channel_id| history.apply(channel_id, &info_json.title));
+
+            let title = match Text::new("Title?")
+                .with_initial_value(initial.as_deref().unwrap_or(&info_json.title))
+                .prompt()
+            {
+                Ok(title) => title,
+                Err(_) => return Ok(Outcome::Cancelled),
+            };
+
+            if let Some(channel_id) = &info_json.channel_id {
+                history.record(channel_id, &info_json.title, &title);
+                if let Err(err) = history.save() {
+                    eprintln!(" -> warning: unable to save the title history: {err}");
+                }
+            }
+
+            title
+        };
+        let title = sanitize_title(&title, config.title_replacements.as_ref());
+        let file_name = match &args.title_template {
+            Some(template) => template.replace("%(title)s", &title),
+            None => format!("{title}.%(ext)s"),
+        };
+
+        if !args.date_buckets {
+            file_name
+        } else if info_json.upload_date.is_some() {
+            format!("%(upload_date>%Y)s/%(upload_date>%m)s/{file_name}")
+        } else {
+            format!("unknown/{file_name}")
+        }
+    };
+
+    let output_dir = if let Some(output_dir) = &args.output_dir {
+        Some(output_dir.clone())
+    } else if args.dirs || config.use_dirs == Some(true) {
+        Some(if matches!(preset, Preset::BestAudio) {
+            dirs::audio_dir().context("cloudn't get the audio directory")?
+        } else {
+            dirs::video_dir().context("couldn't get the video directory")?
+        })
+    } else {
+        None
+    };
+
+    let mut force_overwrites = args.force_overwrites;
+    let output_template = match output_stem(&output_template, info_json.upload_date.as_deref()) {
+        Some(stem) if output_exists(output_dir.as_deref(), &stem) => {
+            let rename = if args.rename_on_conflict {
+                true
+            } else if args.force_overwrites {
+                eprintln!(" -> note: `{stem}` already exists, overwriting it");
+                false
+            } else if args.non_interactive || args.no_overwrites {
+                eprintln!(" -> note: `{stem}` already exists, yt-dlp will skip the download");
+                false
+            } else {
+                let options = vec!["Overwrite", "Rename", "Cancel"];
+                match Select::new(&format!("`{stem}` already exists, what to do?"), options)
+                    .raw_prompt()
+                {
+                    Ok(choice) if choice.index == 0 => {
+                        force_overwrites = true;
+                        false
+                    }
+                    Ok(choice) if choice.index == 1 => true,
+                    _ => return Ok(Outcome::Cancelled),
+                }
+            };
+
+            if rename {
+                let n = (1..)
+                    .find(|n| !output_exists(output_dir.as_deref(), &format!("{stem} ({n})")))
+                    .expect("unbounded range");
+                eprintln!(" -> note: renaming the download to `{stem} ({n})`");
+
+                let template_stem = output_template
+                    .strip_suffix(".%(ext)s")
+                    .unwrap_or(&output_template);
+                format!("{template_stem} ({n}).%(ext)s")
+            } else {
+                output_template
+            }
+        }
+        _ => output_template,
+    };
+
+    let embed_thumbnail = {
+        let default = embed_thumbnail_default(config.embed_thumbnail, preset, has_mutagen);
+
+        if args.non_interactive {
+            default
+        } else {
+            match Confirm::new("Embed thumbnail?")
+                .with_default(default)
+                .prompt()
+            {
+                Ok(confirm) => confirm,
+                Err(_) => return Ok(Outcome::Cancelled),
+            }
+        }
+    };
+
+    if embed_thumbnail && info_json.thumbnails.as_ref().is_none_or(Vec::is_empty) {
+        if let Some(thumbnail) = &info_json.thumbnail {
+            patch_thumbnails(info_json_path, thumbnail)?;
+        }
+    } else if embed_thumbnail && !args.non_interactive {
+        if let Some(thumbnails) = info_json.thumbnails.as_ref().filter(|t| t.len() > 1) {
+            match prep_select_thumbnail(thumbnails.iter()).prompt() {
+                Ok(ThumbnailDisplay(thumbnail)) => {
+                    patch_thumbnails(info_json_path, &thumbnail.url)?
+                }
+                Err(_) => return Ok(Outcome::Cancelled),
+            }
+        }
+    }
+
+    let has_thumbnail = info_json.thumbnail.is_some()
+        || info_json.thumbnails.as_ref().is_some_and(|t| !t.is_empty());
+    let write_thumbnail = if !has_thumbnail {
+        if args.write_thumbnail {
+            eprintln!(" -> note: the media has no thumbnail, not writing it");
+        }
+        false
+    } else if args.write_thumbnail || args.non_interactive || embed_thumbnail {
+        args.write_thumbnail
+    } else {
+        // yt-dlp writes the thumbnail it prefers, the first in the selector order
+        let best = info_json
+            .thumbnails
+            .iter()
+            .flatten()
+            .max_by_key(|t| (t.preference, t.width.unwrap_or(0) * t.height.unwrap_or(0)));
+        let mut confirm = Confirm::new("Save the thumbnail separately?").with_default(false);
+        let help = best.map(|t| format!("thumbnail: {}", ThumbnailDisplay(t).to_string().trim()));
+        if let Some(help) = &help {
+            confirm = confirm.with_help_message(help);
+        }
+        match confirm.prompt() {
+            Ok(confirm) => confirm,
+            Err(_) => return Ok(Outcome::Cancelled),
+        }
+    };
+
+    let embed_chapters = if !matches!(preset, Preset::BestAudio) {
+        let default = config
+            .embed_chapters
+            .unwrap_or(matches!(preset, Preset::Best | Preset::BestVideo));
+
+        if args.non_interactive {
+            default
+        } else {
+            match Confirm::new("Embed chapters?")
+                .with_default(default)
+                .prompt()
+            {
+                Ok(confirm) => confirm,
+                Err(_) => return Ok(Outcome::Cancelled),
+            }
+        }
+    } else {
+        false
+    };
+
+    let chapters = info_json.chapters.as_ref().map_or(0, Vec::len);
+    let split_chapters = if chapters == 0 {
+        if args.split_chapters {
+            eprintln!(" -> note: the media has no chapters, not splitting it");
+        }
+        false
+    } else if args.split_chapters || args.non_interactive {
+        args.split_chapters
+    } else {
+        match Confirm::new(&format!("Split into {chapters} chapters?"))
+            .with_default(false)
+            .prompt()
+        {
+            Ok(confirm) => confirm,
+            Err(_) => return Ok(Outcome::Cancelled),
+        }
+    };
+
+    let description = info_json.description.as_deref().unwrap_or_default();
+    let write_description = if args.write_description || args.non_interactive {
+        args.write_description
+    } else if description.trim().is_empty() || is_short_description(description) {
+        // short descriptions are already in the summary
+        false
+    } else {
+        match Confirm::new("Write the description to a file?")
+            .with_default(false)
+            .prompt()
+        {
+            Ok(confirm) => confirm,
+            Err(_) => return Ok(Outcome::Cancelled),
+        }
+    };
+
+    let mut subs: Vec<SubtitleDisplay> = info_json
+        .subtitles
+        .iter()
+        .flatten()
+        .filter_map(|(lang, s)| match s {
+            infojson::Subtitles::Normal(s) => Some(SubtitleDisplay {
+                lang,
+                name: s.first().and_then(|info| info.name.as_deref()),
+                auto: false,
+            }),
+            _ => None,
+        })
+        .collect();
+    if args.auto_subs {
+        subs.extend(
+            info_json
+                .automatic_captions
+                .iter()
+                .flatten()
+                .map(|(lang, c)| SubtitleDisplay {
+                    lang,
+                    name: c.first().and_then(|info| info.name.as_deref()),
+                    auto: true,
+                }),
+        );
+    }
+
+    let embed_subtitles = if !args.sub_langs.is_empty() {
+        if subs.is_empty() {
+            eprintln!(" -> warning: the media has no subtitles");
+        }
+        let subs = match_sub_langs(&args.sub_langs, subs);
+        (!subs.is_empty()).then_some(subs)
+    } else if !args.non_interactive && !matches!(preset, Preset::BestAudio) && !subs.is_empty() {
+        match prep_multiselect_subtitle(subs).prompt() {
+            Ok(subs) if !subs.is_empty() => Some(subs),
+            Ok(_) => None,
+            Err(_) => return Ok(Outcome::Cancelled),
+        }
+    } else {
+        None
+    };
+
+    let sponsorblock: Option<(SponsorblockMode, Cow<str>)> =
+        if let Some(categories) = &args.sponsorblock {
+            let mode = args.sponsorblock_mode.unwrap_or(SponsorblockMode::Remove);
+            Some((mode, categories.into()))
+        } else if args.non_interactive {
+            args.sponsorblock_mode
+                .map(|mode| (mode, parse::SPONSORBLOCK_CATEGORIES.join(",").into()))
+        } else if info_json.extractor_key.eq_ignore_ascii_case("youtube")
+            && !matches!(preset, Preset::BestAudio)
+        {
+            let mode = match args.sponsorblock_mode {
+                Some(mode) => Some(mode),
+                None => match Select::new(
+                    "What to do with the sponsor blocks?",
+                    vec!["Keep", "Mark as chapters", "Remove"],
+                )
+                .with_help_message("warn: remove will reencode")
+                .raw_prompt()
+                {
+                    Ok(choice) => match choice.index {
+                        1 => Some(SponsorblockMode::Mark),
+                        2 => Some(SponsorblockMode::Remove),
+                        _ => None,
+                    },
+                    Err(_) => return Ok(Outcome::Cancelled),
+                },
+            };
+
+            if let Some(mode) = mode {
+                let all: Vec<usize> = (0..parse::SPONSORBLOCK_CATEGORIES.len()).collect();
+                match MultiSelect::new(
+                    "Which sponsor block categories?",
+                    parse::SPONSORBLOCK_CATEGORIES.to_vec(),
+                )
+                .with_default(&all)
+                .prompt()
+                {
+                    Ok(categories) if !categories.is_empty() => {
+                        Some((mode, categories.join(",").into()))
+                    }
+                    Ok(_) => None,
+                    Err(_) => return Ok(Outcome::Cancelled),
+                }
+            } else {
+                None
+            }
+        } else {
+            None
+        };
+
+    let section = if let Some(section) = args.section {
+        check_section(section, info_json.duration)?;
+        eprintln!(" -> note: downloading a section reencodes the media");
+        Some(section)
+    } else if args.non_interactive
+        || info_json.is_live == Some(true)
+        || info_json.duration.unwrap_or_default() <= LONG_DURATION
+    {
+        None
+    } else {
+        let duration = info_json.duration;
+        match Text::new("Section to download?")
+            .with_help_message("e.g. 10:00-20:00, empty for all, warn: a section will reencode")
+            .with_validator(move |input: &str| {
+                let input = input.trim();
+                let section = match input.is_empty() {
+                    true => Ok(()),
+                    false => parse::section(input).and_then(|section| {
+                        check_section(section, duration).map_err(|err| err.to_string())
+                    }),
+                };
+                Ok(match section {
+                    Ok(()) => Validation::Valid,
+                    Err(err) => Validation::Invalid(err.into()),
+                })
+            })
+            .prompt()
+        {
+            Ok(input) if input.trim().is_empty() => None,
+            Ok(input) => Some(parse::section(input.trim()).map_err(anyhow::Error::msg)?),
+            Err(_) => return Ok(Outcome::Cancelled),
+        }
+    };
+
+    let extras: Cow<[String]> = if args.edit_extras {
+        let initial = args
+            .extras
+            .iter()
+            .map(|extra| shell::quote(extra.as_ref()))
+            .collect::<Vec<_>>()
+            .join(" ");
+
+        match Text::new("Extra yt-dlp arguments?")
+            .with_initial_value(&initial)
+            .prompt()
+        {
+            Ok(edited) => match shlex::split(&edited) {
+                Some(extras) => extras.into(),
+                None => bail!("invalid quoting in the extra arguments: {edited}"),
+            },
+            Err(_) => return Ok(Outcome::Cancelled),
+        }
+    } else {
+        (&*args.extras).into()
+    };
+
+    check_extras(preset, &extras)?;
+
+    let format_spec = format_spec(&formats);
+
+    if format_spec.is_empty() {
+        bail!("no format selected");
+    }
+
+    match estimated_size(preset, &selected_formats, &info_json.formats) {
+        Some(size) => eprintln!(
+            " -> note: estimated download size: {}",
+            SizeFormatter::new(size, BINARY)
+        ),
+        None => eprintln!(" -> note: estimated download size: unknown"),
+    }
+
+    let mut command = Command::new(&args.yt_dlp);
+
+    if args.quiet {
+        command.arg("--quiet");
+    }
+
+    if let Some(output_dir) = &output_dir {
+        command.arg("-P").arg(output_dir);
+    }
+
+    add_cookies(&mut command, args, cookies_from_browser);
+
+    if matches!(preset, Preset::BestAudio) {
+        command.arg("-x");
+        if let Some(audio_format) = args.audio_format {
+            command.arg("--audio-format").arg(value_name(audio_format));
+        }
+        if let Some(audio_quality) = audio_quality {
+            command
+                .arg("--audio-quality")
+                .arg(audio_quality.to_string());
+        }
+    }
+
+    if embed_thumbnail {
+        command.arg("--embed-thumbnail");
+    } else {
+        command.arg("--no-embed-thumbnail");
+    }
+
+    if write_thumbnail {
+        command.arg("--write-thumbnail");
+    }
+
+    let (chapters_arg, warning) =
+        embed_chapters_arg(embed_chapters, sponsorblock.as_ref().map(|(mode, _)| *mode));
+    if let Some(warning) = warning {
+        eprintln!(" -> warning: {warning}");
+    }
+    command.arg(chapters_arg);
+
+    if write_description {
+        command.arg("--write-description");
+    }
+
+    if split_chapters {
+        let stem = output_template
+            .strip_suffix(".%(ext)s")
+            .unwrap_or(&output_template);
+        command.arg("--split-chapters").arg("-o").arg(format!(
+            "chapter:{stem} - %(section_number)02d %(section_title)s.%(ext)s"
+        ));
+    }
+
+    if let Some((mode, categories)) = &sponsorblock {
+        match mode {
+            SponsorblockMode::Mark => command.arg("--sponsorblock-mark"),
+            SponsorblockMode::Remove => command.arg("--sponsorblock-remove"),
+        };
+        command.arg(&**categories);
+        if let Some(api) = &args.sponsorblock_api {
+            command.arg("--sponsorblock-api").arg(api);
+        }
+    } else {
+        if args.sponsorblock_api.is_some() {
+            eprintln!(" -> note: sponsorblock is disabled, the sponsorblock api is unused");
+        }
+        command.arg("--no-sponsorblock");
+    }
+
+    if let Some(section) = section {
+        command.arg("--download-sections").arg(section.spec());
+    }
+
+    let embed_metadata = if args.embed_metadata {
+        true
+    } else if args.no_embed_metadata {
+        false
+    } else {
+        config
+            .embed_metadata
+            .unwrap_or(is_music && matches!(preset, Preset::BestAudio))
+    };
+
+    if embed_metadata {
+        command.arg("--embed-metadata");
+        if is_music {
+            command
+                .arg("--parse-metadata")
+                .arg("%(artist,uploader)s:%(artist)s");
+        }
+    }
+
+    if args.abort_on_unavailable_fragments {
+        command.arg("--abort-on-unavailable-fragments");
+    } else {
+        command.arg("--skip-unavailable-fragments");
+    }
+
+    if let Some(concurrent_fragments) = args.concurrent_fragments {
+        // the presets select their formats with a selector, any format may be chosen
+        let candidates = if selected_formats.is_empty() {
+            info_json.formats.iter().collect()
+        } else {
+            selected_formats.clone()
+        };
+        if candidates.iter().any(|f| f.is_fragmented()) {
+            command
+                .arg("--concurrent-fragments")
+                .arg(concurrent_fragments.to_string());
+        } else {
+            eprintln!(" -> note: the formats aren't fragmented, ignoring --concurrent-fragments");
+        }
+    }
+
+    let http_headers: BTreeMap<&str, &str> = selected_formats
+        .iter()
+        .filter_map(|f| f.http_headers.as_ref())
+        .flatten()
+        .map(|(name, value)| (name.as_str(), value.as_str()))
+        .collect();
+    for (name, value) in http_headers {
+        command.arg("--add-header").arg(format!("{name}:{value}"));
+    }
+
+    command.args(overwrite_args(
+        force_overwrites,
+        args.no_post_overwrites,
+        &extras,
+    ));
+
+    if args.xattrs {
+        eprintln!(" -> note: xattrs are skipped on filesystems not supporting them");
+        command.arg("--xattrs");
+    }
+
+    for retry_sleep in &args.retry_sleep {
+        command.arg("--retry-sleep").arg(retry_sleep);
+    }
+
+    if let Some(throttled_rate) = &args.throttled_rate {
+        command.arg("--throttled-rate").arg(throttled_rate);
+    }
+
+    if let Some(limit_rate) = &args.limit_rate {
+        command.arg("--limit-rate").arg(limit_rate);
+    }
+
+    if let Some(archive) = &args.archive {
+        command.arg("--download-archive").arg(archive);
+        if args.break_on_existing {
+            command.arg("--break-on-existing");
+        }
+    }
+
+    if let Some(max_filesize) = args.max_filesize {
+        command.arg("--max-filesize").arg(max_filesize.to_string());
+    }
+
+    if let Some(format_sort) = &args.format_sort {
+        command.arg("-S").arg(format_sort);
+    }
+
+    let needs_merge = match preset {
+        Preset::Best => true,
+        Preset::BestAudio | Preset::BestVideo => false,
+        Preset::Custom => selected_formats.len() > 1,
+        Preset::Manual => format_spec.contains('+'),
+    };
+
+    let chosen_container = args.merge_output_format.or(args.prefer_container);
+
+    if let Some(embed_subs) = &embed_subtitles {
+        command.arg("--embed-subs");
+        if let Some(sub_format) = &args.sub_format {
+            command.arg("--sub-format").arg(sub_format);
+        }
+        if chosen_container.is_none() && needs_merge {
+            eprintln!(" -> note: merging into mkv to preserve the embedded subtitles");
+        }
+        if embed_subs.iter().any(|sub| sub.auto) {
+            command.arg("--write-auto-subs");
+        }
+        let langs: BTreeSet<&str> = embed_subs.iter().map(|sub| sub.lang).collect();
+        for sublang in langs {
+            command.arg("--sub-lang");
+            command.arg(sublang);
+        }
+    }
+
+    if let Some(container) = args.merge_output_format {
+        if selected_formats.is_empty() {
+            // the presets select their formats with a selector, any format may be chosen
+            let candidates: Vec<_> = info_json.formats.iter().collect();
+            if let Some(codec) = container.incompatible_codec(&candidates) {
+                eprintln!(
+                    " -> warning: {codec} may be selected but isn't well supported in {}, consider --merge-output-format mkv",
+                    value_name(container)
+                );
+            }
+        } else if let Some(codec) = container.incompatible_codec(&selected_formats) {
+            eprintln!(
+                " -> warning: {codec} isn't well supported in {}, consider --merge-output-format mkv",
+                value_name(container)
+            );
+        }
+    }
+
+    let merge_output_format = merge_output_format(
+        preset,
+        chosen_container,
+        embed_subtitles.is_some(),
+        needs_merge,
+    );
+    if let Some(merge_output_format) = merge_output_format {
+        if !has_extra(&extras, "--merge-output-format") {
+            command
+                .arg("--merge-output-format")
+                .arg(value_name(merge_output_format));
+        }
+    }
+
+    if let Some(recode_video) = args.recode_video {
+        eprintln!(" -> warning: recoding reencodes the video, which is slow and lossy");
+        command.arg("--recode-video").arg(value_name(recode_video));
+    }
+
+    let after = args
+        .after
+        .or((args.non_interactive || args.dry_run).then_some(After::None));
+
+    let downloaded_path = tempdir.join("downloaded.json");
+    if args.print_json_after || after != Some(After::None) {
+        command
+            .arg("--print-to-file")
+            .arg("after_move:%(.{filepath,format_id,duration})j")
+            .arg(&downloaded_path);
+    }
+
+    if !args.playlist {
+        command.arg("--no-playlist");
+        if args.write_playlist_metafiles || args.no_write_playlist_metafiles {
+            eprintln!(" -> warning: the playlist metafiles are only written with --playlist");
+        }
+    } else if args.write_playlist_metafiles {
+        command.arg("--write-playlist-metafiles");
+    } else if args.no_write_playlist_metafiles {
+        command.arg("--no-write-playlist-metafiles");
+    }
+
+    command
+        .arg("--load-info-json")
+        .arg(info_json_path)
+        .arg("-o")
+        .arg(&output_template)
+        .arg("-f")
+        .arg(&format_spec)
+        .args(&*extras);
+
+    if let Some(hook) = &args.command_hook {
+        run_command_hook(hook, &mut command)?;
+    }
+
+    if args.print_json_after || args.print_json {
+        // keep stdout for the JSON output
+        command.stdout(std::io::stderr());
+    }
+
+    if args.dry_run {
+        println!("{}", shell::command_line(&command));
+    } else if args.print_command {
+        let reusable = reusable_command(&command, tempdir, &info_json.webpage_url);
+        println!("{}", shell::command_line(&reusable));
+    }
+
+    if args.dry_run {
+        return Ok(Outcome::Downloaded(preset));
+    }
+
+    if args.verbose > 0 {
+        eprintln!(" -> executing: {:?}", command);
+    }
+
+    let status = command.status()?;
+
+    if !status.success() {
+        bail!("yt-dlp error: {:?}", command);
+    }
+
+    if args.print_json_after {
+        let downloaded = read_downloaded(&downloaded_path)?;
+        println!("{}", serde_json::to_string(&downloaded)?);
+    }
+
+    let after = match after {
+        Some(after) => after,
+        None => {
+            let options = vec![After::None, After::Play, After::Open];
+            match Select::new("What to do now?", options).prompt() {
+                Ok(after) => after,
+                Err(_) => After::None,
+            }
+        }
+    };
+    if after != After::None {
+        let downloaded = read_downloaded(&downloaded_path)?;
+        let path = match after {
+            After::Open => directory_of(&downloaded.filepath),
+            _ => &downloaded.filepath,
+        };
+        open_path(path)?;
+    }
+
+    if args.print_json {
+        let summary = DownloadSummary {
+            title: &info_json.title,
+            webpage_url: &info_json.webpage_url,
+            format: &format_spec,
+            format_ids: selected_formats.iter().map(|f| &*f.format_id).collect(),
+            output_template: &output_template,
+        };
+        println!("{}", serde_json::to_string(&summary)?);
+    }
+
+    Ok(Outcome::Downloaded(preset))
+}
+
+/// Open the file or directory with the default application of the system
+fn open_path(path: &Path) -> Result<(), anyhow::Error> {
+    let mut command = if cfg!(target_os = "macos") {
+        Command::new("open")
+    } else if cfg!(windows) {
+        let mut command = Command::new("cmd");
+        command.args(["/C", "start", ""]);
+        command
+    } else {
+        Command::new("xdg-open")
+    };
+
+    command
+        .arg(path)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .with_context(|| format!("unable to open: {}", path.display()))?;
+    Ok(())
+}
+
+/// Description of a download, for `--print-json`
+#[derive(Debug, Serialize)]
+struct DownloadSummary<'a> {
+    title: &'a str,
+    webpage_url: &'a str,
+    /// Format spec given to yt-dlp
+    format: &'a str,
+    /// Ids of the formats selected in the prompts, empty with the presets using a selector
+    format_ids: Vec<&'a str>,
+    output_template: &'a str,
+}
+
+/// Downloaded file, as printed by yt-dlp after moving it to its final place
+#[derive(Debug, Serialize, Deserialize)]
+struct Downloaded {
+    filepath: PathBuf,
+    format_id: String,
+    #[serde(default)]
+    filesize: Option<u64>,
+    duration: Option<f64>,
+}
+
+/// Read the last downloaded file printed by yt-dlp and stat it for its size
+fn read_downloaded(path: &Path) -> Result<Downloaded, anyhow::Error> {
+    let printed = std::fs::read_to_string(path)
+        .with_context(|| format!("unable to read file: {}", path.display()))?;
+    let last = printed
+        .lines()
+        .last()
+        .context("yt-dlp didn't print the downloaded file")?;
+
+    let mut downloaded: Downloaded =
+        serde_json::from_str(last).context("unable to read the downloaded file information")?;
+    downloaded.filesize = std::fs::metadata(&downloaded.filepath)
+        .ok()
+        .map(|metadata| metadata.len());
+
+    Ok(downloaded)
+}
+
+/// Presets offered depending on the availability of audio-only and video-only formats
+fn available_presets(formats: &[infojson::Format]) -> &'static [Preset] {
+    let has_some_video_only_format = formats
+        .iter()
+        .any(|f| f.vcodec.is_some() && f.acodec.is_none());
+    let has_some_audio_only_format = formats
+        .iter()
+        .any(|f| f.vcodec.is_none() && f.acodec.is_some());
+
+    if has_some_audio_only_format && has_some_video_only_format {
+        &[
+            Preset::Manual,
+            Preset::Custom,
+            Preset::Best,
+            Preset::BestAudio,
+            Preset::BestVideo,
+        ]
+    } else if has_some_audio_only_format {
+        &[
+            Preset::Manual,
+            Preset::Custom,
+            Preset::Best,
+            Preset::BestAudio,
+        ]
+    } else if has_some_video_only_format {
+        &[
+            Preset::Manual,
+            Preset::Custom,
+            Preset::Best,
+            Preset::BestVideo,
+        ]
+    } else {
+        &[Preset::Manual, Preset::Custom, Preset::Best]
+    }
+}
+
+/// Filter over the formats offered in the selectors
+struct FormatFilter<'a> {
+    name: Cow<'static, str>,
+    keep: Box<dyn Fn(&infojson::Format) -> bool + 'a>,
+}
+
+impl<'a> FormatFilter<'a> {
+    fn new(
+        name: impl Into<Cow<'static, str>>,
+        keep: impl Fn(&infojson::Format) -> bool + 'a,
+    ) -> Self {
+        FormatFilter {
+            name: name.into(),
+            keep: Box::new(keep),
+        }
+    }
+}
+
+/// Container to merge the video and audio into, `None` when they aren't merged
+///
+/// Defaults to mkv, which takes any codecs and subtitles as-is unlike mp4 and webm, when
+/// embedding subtitles or with the best preset.
+fn merge_output_format(
+    preset: Preset,
+    chosen: Option<Container>,
+    embed_subtitles: bool,
+    needs_merge: bool,
+) -> Option<Container> {
+    if !needs_merge {
+        return None;
+    }
+
+    chosen.or((embed_subtitles || preset == Preset::Best).then_some(Container::Mkv))
+}
+
+/// Format spec for `-f`, joining the selected formats without the blank ones
+fn format_spec(formats: &[Cow<str>]) -> String {
+    formats
+        .iter()
+        .map(|f| f.trim())
+        .filter(|f| !f.is_empty())
+        .collect::<Vec<_>>()
+        .join("+")
+}
+
+/// Filters of the video selector, notifying about the formats they hide
+fn video_filters<'a>(args: &'a Args, formats: &[infojson::Format]) -> Vec<FormatFilter<'a>> {
+    let mut filters = vec![FormatFilter::new("video codec", |f| {
+        f.vcodec.is_some() /*&& f.acodec.is_none()*/
+            || (args.show_storyboards && f.is_storyboard())
+    })];
+    if !args.show_storyboards {
+        filters.push(FormatFilter::new("storyboard", |f| !f.is_storyboard()));
+    }
+    if !args.allow_drm {
+        filters.push(FormatFilter::new("DRM protection", |f| {
+            f.has_drm != Some(true)
+        }));
+    }
+    if let Some(max_filesize) = args.max_filesize {
+        let hidden = formats
+            .iter()
+            .filter(|f| f.size().is_some_and(|size| size > max_filesize))
+            .count();
+        if hidden > 0 {
+            eprintln!(" -> note: hiding {hidden} format(s) larger than --max-filesize");
+        }
+        filters.push(FormatFilter::new("--max-filesize", move |f| {
+            f.size().is_none_or(|size| size <= max_filesize)
+        }));
+    }
+    if let Some(min_fps) = args.min_fps {
+        if !args.lenient {
+            let unknown_fps = formats
+                .iter()
+                .filter(|f| f.vcodec.is_some() && f.fps.is_none())
+                .count();
+            if unknown_fps > 0 {
+                eprintln!(
+                    " -> warning: excluding {unknown_fps} format(s) with an unknown framerate"
+                );
+            }
+        }
+        filters.push(FormatFilter::new("--min-fps", move |f| {
+            f.fps.map_or(args.lenient, |fps| fps >= min_fps as f64)
+        }));
+    }
+
+    filters
+}
+
+/// Formats with a `format_id` kept by all the filters
+///
+/// When the filters exclude every format, report which ones did and relax them,
+/// with `lenient` or after asking. Returns `None` when the user aborts.
+fn filter_formats<'f>(
+    kind: &str,
+    formats: &'f [infojson::Format],
+    filters: &[FormatFilter],
+    lenient: bool,
+) -> Result<Option<Vec<&'f infojson::Format>>, anyhow::Error> {
+    let formats: Vec<&infojson::Format> = formats
+        .iter()
+        .filter(|f| !f.format_id.trim().is_empty())
+        .collect();
+
+    let filtered: Vec<&infojson::Format> = formats
+        .iter()
+        .copied()
+        .filter(|f| filters.iter().all(|filter| (filter.keep)(f)))
+        .collect();
+
+    if !filtered.is_empty() {
+        return Ok(Some(filtered));
+    }
+    if formats.is_empty() {
+        bail!("no {kind} format available");
+    }
+
+    let removed_by: Vec<String> = filters
+        .iter()
+        .filter_map(|filter| {
+            let removed = formats.iter().filter(|f| !(filter.keep)(f)).count();
+            (removed > 0).then(|| format!("{} ({removed})", filter.name))
+        })
+        .collect();
+    eprintln!(
+        " -> warning: no {kind} format left after filtering by: {}",
+        removed_by.join(", ")
+    );
+
+    if lenient {
+        eprintln!(" -> note: relaxing the {kind} format filters");
+        return Ok(Some(formats));
+    }
+
+    match Confirm::new(&format!("Show the {kind} formats without filters?"))
+        .with_default(true)
+        .prompt()
+    {
+        Ok(true) => Ok(Some(formats)),
+        Ok(false) | Err(_) => Ok(None),
+    }
+}
+
+/// Duration in seconds above which the section to download is asked
+const LONG_DURATION: f64 = 30.0 * 60.0;
+
+/// Reject the sections outside of the media
+fn check_section(section: parse::Section, duration: Option<f64>) -> Result<(), anyhow::Error> {
+    let Some(duration) = duration else {
+        return Ok(());
+    };
+
+    if section.start >= duration || section.end.is_some_and(|end| end > duration) {
+        bail!(
+            "the section {} exceeds the duration of the media ({duration}s)",
+            section.spec()
+        );
+    }
+    Ok(())
+}
+
+/// Whether the description is short enough to be printed in the media summary
+fn is_short_description(description: &str) -> bool {
+    let description = description.trim();
+    description.chars().count() <= 120 && !description.contains('\n')
+}
+
+/// Output path of the template without the extension, if it can be resolved before the download
+fn output_stem(output_template: &str, upload_date: Option<&str>) -> Option<String> {
+    let mut stem = output_template
+        .strip_suffix(".%(ext)s")
+        .unwrap_or(output_template)
+        .to_string();
+
+    if let Some(date) = upload_date.filter(|date| date.len() == 8 && date.is_ascii()) {
+        stem = stem
+            .replace("%(upload_date>%Y)s", &date[..4])
+            .replace("%(upload_date>%m)s", &date[4..6]);
+    }
+
+    (!stem.contains("%(")).then_some(stem)
+}
+
+/// Directory of the file, the current one for a bare file name like yt-dlp prints without `-o`
+fn directory_of(path: &Path) -> &Path {
+    path.parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+        .unwrap_or(Path::new("."))
+}
+
+/// Whether a file `<stem>.<ext>` exists in the output directory, ignoring partial downloads
+fn output_exists(output_dir: Option<&Path>, stem: &str) -> bool {
+    let path = output_dir.unwrap_or(Path::new(".")).join(stem);
+    let (Some(parent), Some(name)) = (path.parent(), path.file_name()) else {
+        return false;
+    };
+    let prefix = format!("{}.", name.to_string_lossy());
+
+    let Ok(entries) = std::fs::read_dir(parent) else {
+        return false;
+    };
+    entries.flatten().any(|entry| {
+        entry
+            .file_name()
+            .to_string_lossy()
+            .strip_prefix(&prefix)
+            .is_some_and(|ext| {
+                !ext.is_empty() && !ext.contains('.') && ext != "part" && ext != "ytdl"
+            })
+    })
+}
+
+/// Make the title safe to use as a file name in an output template
+///
+/// Path separators are replaced by look-alikes (overridable by the config), control
+/// characters are removed and `%` is escaped for the template.
+fn sanitize_title(title: &str, replacements: Option<&BTreeMap<String, String>>) -> String {
+    let mut sanitized: String = title.chars().filter(|c| !c.is_control()).collect();
+
+    let defaults = [("/", "⧸"), ("\\", "⧹")];
+    for (from, to) in defaults {
+        if !replacements.is_some_and(|r| r.contains_key(from)) {
+            sanitized = sanitized.replace(from, to);
+        }
+    }
+    for (from, to) in replacements.into_iter().flatten() {
+        if !from.is_empty() {
+            sanitized = sanitized.replace(from.as_str(), to);
+        }
+    }
+
+    sanitized.replace('%', "%%")
+}
+
+/// Release date for display, in local time when a precise timestamp is available
+fn release_date(info_json: &infojson::InfoJson) -> Option<String> {
+    if let Some(timestamp) = info_json.precise_timestamp() {
+        if let Some(date) = DateTime::from_timestamp(timestamp as i64, 0) {
+            return Some(
+                date.with_timezone(&Local)
+                    .format("%Y-%m-%d %H:%M")
+                    .to_string(),
+            );
+        }
+    }
+
+    let upload_date = info_json.upload_date.as_deref()?;
+    NaiveDate::parse_from_str(upload_date, "%Y%m%d")
+        .ok()
+        .map(|date| date.format("%Y-%m-%d").to_string())
+}
+
+/// Effective settings as TOML, with the layer each value comes from as a comment
+///
+/// The layers are the defaults, the config file, the profile, the environment and the flags.
+fn config_dump(
+    args: &Args,
+    matches: &ArgMatches,
+    config: &config::Config,
+    config_path: Option<&Path>,
+    profile: Option<&config::Profile>,
+) -> String {
+    let string = |value: &str| toml::Value::from(value).to_string();
+    let table = |map: &BTreeMap<String, String>| {
+        let entries: Vec<String> = map
+            .iter()
+            .map(|(key, value)| format!("{} = {}", string(key), string(value)))
+            .collect();
+        format!("{{ {} }}", entries.join(", "))
+    };
+    let profile_name = args.profile.as_deref().unwrap_or_default();
+    let config_source = |in_profile: bool, value_is_set: bool| {
+        if in_profile {
+            format!("profile {profile_name}")
+        } else if value_is_set {
+            "config file".to_string()
+        } else {
+            "default".to_string()
+        }
+    };
+    let flag_source = |id: &str, in_profile: bool| match matches.value_source(id) {
+        Some(ValueSource::CommandLine) => format!("--{}", id.replace('_', "-")),
+        Some(ValueSource::EnvVariable) => "environment".to_string(),
+        _ if in_profile => format!("profile {profile_name}"),
+        _ => "default".to_string(),
+    };
+
+    let mut lines = vec![match config_path {
+        Some(path) if path.exists() => format!("# config file: {}", path.display()),
+        Some(path) => format!("# config file: {} (missing)", path.display()),
+        None => "# config file: none".to_string(),
+    }];
+    if let Some(name) = &args.profile {
+        lines.push(format!("# profile: {name}"));
+    }
+    let mut push = |key: &str, value: Option<String>, source: String| {
+        lines.push(match value {
+            Some(value) => format!("{key} = {value} # {source}"),
+            None => format!("# {key} is unset"),
+        })
+    };
+    let in_profile = |set: fn(&config::Profile) -> bool| profile.is_some_and(set);
+
+    push(
+        "default_preset",
+        config
+            .default_preset
+            .map(|preset| string(&value_name(preset))),
+        config_source(
+            in_profile(|p| p.default_preset.is_some()),
+            config.default_preset.is_some(),
+        ),
+    );
+    push(
+        "site_presets",
+        config.site_presets.as_ref().map(|presets| {
+            table(
+                &presets
+                    .iter()
+                    .map(|(site, &preset)| (site.clone(), value_name(preset)))
+                    .collect(),
+            )
+        }),
+        config_source(false, config.site_presets.is_some()),
+    );
+    for (key, value, in_profile) in [
+        (
+            "embed_thumbnail",
+            config.embed_thumbnail,
+            in_profile(|p| p.embed_thumbnail.is_some()),
+        ),
+        (
+            "embed_chapters",
+            config.embed_chapters,
+            in_profile(|p| p.embed_chapters.is_some()),
+        ),
+        (
+            "use_dirs",
+            config.use_dirs,
+            in_profile(|p| p.use_dirs.is_some()),
+        ),
+        (
+            "embed_metadata",
+            config.embed_metadata,
+            in_profile(|p| p.embed_metadata.is_some()),
+        ),
+    ] {
+        push(
+            key,
+            value.map(|value| value.to_string()),
+            config_source(in_profile, value.is_some()),
+        );
+    }
+    push(
+        "title_replacements",
+        config.title_replacements.as_ref().map(table),
+        config_source(false, config.title_replacements.is_some()),
+    );
+    push(
+        "rate_limits",
+        config.rate_limits.as_ref().map(|windows| {
+            let windows: Vec<String> = windows
+                .iter()
+                .map(|w| {
+                    string(&format!(
+                        "{}-{}={}",
+                        w.start.format("%H:%M"),
+                        w.end.format("%H:%M"),
+                        w.rate
+                    ))
+                })
+                .collect();
+            format!("[{}]", windows.join(", "))
+        }),
+        config_source(false, config.rate_limits.is_some()),
+    );
+
+    push(
+        "quality",
+        args.quality.map(|quality| quality.to_string()),
+        flag_source("quality", in_profile(|p| p.quality.is_some())),
+    );
+    push(
+        "prefer_codec",
+        args.prefer_codec.map(|codec| string(&value_name(codec))),
+        flag_source("prefer_codec", in_profile(|p| p.prefer_codec.is_some())),
+    );
+    push(
+        "prefer_container",
+        args.prefer_container
+            .map(|container| string(&value_name(container))),
+        flag_source(
+            "prefer_container",
+            in_profile(|p| p.prefer_container.is_some()),
+        ),
+    );
+    push(
+        "merge_output_format",
+        args.merge_output_format
+            .map(|container| string(&value_name(container))),
+        flag_source(
+            "merge_output_format",
+            in_profile(|p| p.merge_output_format.is_some()),
+        ),
+    );
+    push(
+        "max_filesize",
+        args.max_filesize.map(|size| size.to_string()),
+        flag_source("max_filesize", in_profile(|p| p.max_filesize.is_some())),
+    );
+    push(
+        "limit_rate",
+        args.limit_rate.as_deref().map(string),
+        match matches.value_source("limit_rate") {
+            Some(ValueSource::CommandLine) => "--limit-rate".to_string(),
+            _ => "config rate_limits".to_string(),
+        },
+    );
+    push(
+        "yt_dlp",
+        Some(string(&args.yt_dlp.to_string_lossy())),
+        match matches.value_source("yt_dlp") {
+            Some(ValueSource::EnvVariable) => "YT_DLP_BINARY".to_string(),
+            _ => flag_source("yt_dlp", false),
+        },
+    );
+
+    lines.iter().map(|line| format!("{line}\n")).collect()
+}
+
+/// yt-dlp flag embedding the chapters or not, with a warning when overriding the choice
+///
+/// The marked sponsor blocks are chapters, so they are always embedded in mark mode.
+fn embed_chapters_arg(
+    embed_chapters: bool,
+    sponsorblock: Option<SponsorblockMode>,
+) -> (&'static str, Option<&'static str>) {
+    match (embed_chapters, sponsorblock) {
+        (true, _) => ("--embed-chapters", None),
+        (false, Some(SponsorblockMode::Mark)) => (
+            "--embed-chapters",
+            Some("embedding the chapters anyway, the sponsor blocks are marked as chapters"),
+        ),
+        (false, _) => ("--no-embed-chapters", None),
+    }
+}
+
+/// Download command without the arguments pointing into the temporary directory
+///
+/// The info json is replaced by the url of the media and md's own `--print-to-file` is dropped,
+/// so the command can be run after md deleted the directory.
+fn reusable_command(command: &Command, tempdir: &Path, webpage_url: &str) -> Command {
+    let in_tempdir =
+        |arg: Option<&OsStr>| arg.is_some_and(|arg| Path::new(arg).starts_with(tempdir));
+
+    let mut reusable = Command::new(command.get_program());
+    let mut args = command.get_args();
+    while let Some(arg) = args.next() {
+        match arg.to_str() {
+            Some("--load-info-json") => {
+                let path = args.next();
+                if in_tempdir(path) {
+                    reusable.arg(webpage_url);
+                } else {
+                    reusable.arg(arg).args(path);
+                }
+            }
+            Some("--print-to-file") => {
+                let (template, path) = (args.next(), args.next());
+                if !in_tempdir(path) {
+                    reusable.arg(arg).args(template).args(path);
+                }
+            }
+            _ => {
+                reusable.arg(arg);
+            }
+        }
+    }
+    reusable
+}
+
+/// Name of the value as passed on the command-line
+fn value_name<T: ValueEnum>(value: T) -> String {
+    value
+        .to_possible_value()
+        .expect("no skipped value")
+        .get_name()
+        .to_string()
+}
+
+/// Selector preferring formats with the given video and audio extensions
+///
+/// Returns `None` when the formats don't contain what the preset needs with those extensions.
+fn prefer_container_selector(
+    preset: Preset,
+    (video_ext, audio_ext): (&str, &str),
+    video_filter: &str,
+    formats: &[infojson::Format],
+) -> Option<String> {
+    let has_video = formats
+        .iter()
+        .any(|f| f.vcodec.is_some() && f.ext == video_ext);
+    let has_audio = formats
+        .iter()
+        .any(|f| f.acodec.is_some() && f.vcodec.is_none() && f.ext == audio_ext);
+
+    match preset {
+        Preset::BestAudio if has_audio => Some(format!("bestaudio[ext={audio_ext}]")),
+        Preset::BestVideo if has_video => {
+            Some(format!("bestvideo[ext={video_ext}]{video_filter}"))
+        }
+        Preset::Best if has_video && has_audio => Some(format!(
+            "bv*[ext={video_ext}]{video_filter}+ba[ext={audio_ext}]/b[ext={video_ext}]{video_filter}"
+        )),
+        _ => None,
+    }
+}
+
+/// Combined size of the selected formats, or of the formats the preset most likely selects
+///
+/// yt-dlp lists the formats from worst to best, so the last matching format is assumed to be
+/// the best one. Returns `None` when any of the sizes is unknown.
+fn estimated_size(
+    preset: Preset,
+    selected_formats: &[&infojson::Format],
+    formats: &[infojson::Format],
+) -> Option<u64> {
+    let best = |keep: fn(&infojson::Format) -> bool| {
+        formats.iter().rev().find(|f| keep(f) && !f.is_storyboard())
+    };
+    let best_video = || best(|f| f.vcodec.is_some() && f.acodec.is_none());
+    let best_audio = || best(|f| f.acodec.is_some() && f.vcodec.is_none());
+
+    let estimated: Vec<&infojson::Format> = match preset {
+        Preset::Custom => selected_formats.to_vec(),
+        Preset::BestAudio => vec![best_audio()?],
+        Preset::BestVideo => vec![best(|f| f.vcodec.is_some())?],
+        Preset::Best => match (best_video(), best_audio()) {
+            (Some(video), Some(audio)) => vec![video, audio],
+            _ => vec![best(|f| f.vcodec.is_some() && f.acodec.is_some())?],
+        },
+        Preset::Manual => return None,
+    };
+
+    if estimated.is_empty() {
+        return None;
+    }
+
+    estimated.into_iter().map(infojson::Format::size).sum()
+}
+
+/// Reject the extra arguments known to conflict with the preset
+///
+/// The best-audio preset extracts the audio (`-x`), so the video remuxing, recoding, merging
+/// and subtitles flags don't apply, while the other presets can't extract the audio.
+fn check_extras(preset: Preset, extras: &[String]) -> Result<(), anyhow::Error> {
+    const AUDIO_CONFLICTS: &[&str] = &[
+        "--remux-video",
+        "--recode-video",
+        "--merge-output-format",
+        "--embed-subs",
+    ];
+    const VIDEO_CONFLICTS: &[&str] = &["-x", "--extract-audio"];
+
+    let (conflicts, hint) = match preset {
+        Preset::BestAudio => (
+            AUDIO_CONFLICTS,
+            "the best-audio preset extracts the audio, remove it or use another preset",
+        ),
+        Preset::Best | Preset::BestVideo | Preset::Custom => (
+            VIDEO_CONFLICTS,
+            "use the best-audio preset to extract the audio",
+        ),
+        Preset::Manual => return Ok(()),
+    };
+
+    if let Some(flag) = conflicts.iter().find(|flag| has_extra(extras, flag)) {
+        bail!(
+            "`{flag}` in the extra arguments conflicts with the {} preset: {hint} (checked flags: {})",
+            PresetDisplay(preset),
+            conflicts.join(", ")
+        );
+    }
+
+    Ok(())
+}
+
+/// Default answer of the "Embed thumbnail?" prompt
+///
+/// The `embed_thumbnail` config forces it, the mutagen heuristic only applies when it's unset.
+fn embed_thumbnail_default(
+    config: Option<bool>,
+    preset: Preset,
+    has_mutagen: impl FnOnce() -> bool,
+) -> bool {
+    config
+        .unwrap_or_else(|| matches!(preset, Preset::BestAudio | Preset::BestVideo) && has_mutagen())
+}
+
+/// Whether mutagen, used by yt-dlp to embed the thumbnails in some formats, is in the PATH
+fn has_mutagen() -> bool {
+    static HAS_MUTAGEN: OnceLock<bool> = OnceLock::new();
+    *HAS_MUTAGEN.get_or_init(|| which::which("mutagen-inspect").is_ok())
+}
+
+/// Whether to color the prompts, not when disabled or when they aren't shown in a terminal
+fn use_color(args: &Args) -> bool {
+    !args.no_color
+        && std::env::var_os("NO_COLOR").is_none_or(|no_color| no_color.is_empty())
+        && std::io::stderr().is_terminal()
+}
+
+/// yt-dlp flags applying the overwrite choice to the download and the post-processed files
+///
+/// Overwrite flags already in the extras take precedence over the choice.
+fn overwrite_args(
+    force_overwrites: bool,
+    no_post_overwrites: bool,
+    extras: &[String],
+) -> Vec<&'static str> {
+    let overwrites_in_extras = [
+        "--force-overwrites",
+        "--no-force-overwrites",
+        "--no-overwrites",
+        "-w",
+    ]
+    .iter()
+    .any(|flag| has_extra(extras, flag));
+
+    let mut args = Vec::new();
+    if no_post_overwrites
+        || (overwrites_in_extras
+            && (has_extra(extras, "--no-overwrites") || has_extra(extras, "-w")))
+        || (!overwrites_in_extras && !force_overwrites)
+    {
+        args.push("--no-post-overwrites");
+    }
+    if !overwrites_in_extras {
+        // protect the existing files unless overwriting them was chosen
+        args.push(if force_overwrites {
+            "--force-overwrites"
+        } else {
+            "--no-overwrites"
+        });
+    }
+    args
+}
+
+/// Whether the extra arguments contain the given flag (`--flag value` or `--flag=value`)
+fn has_extra(extras: &[String], flag: &str) -> bool {
+    extras.iter().any(|extra| {
+        extra == flag
+            || extra
+                .strip_prefix(flag)
+                .is_some_and(|rest| rest.starts_with('='))
+    })
+}
+
+/// Entries (`extractor id`) of a yt-dlp download archive, a missing file being empty
+fn read_archive(path: &Path) -> Result<HashSet<String>, anyhow::Error> {
+    match std::fs::read_to_string(path) {
+        Ok(archive) => Ok(archive
+            .lines()
+            .map(|line| line.trim().to_string())
+            .collect()),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(HashSet::new()),
+        Err(err) => {
+            Err(err).with_context(|| format!("unable to read the archive: {}", path.display()))
+        }
+    }
+}
+
+/// Id of the video of a YouTube URL, to check the archive without fetching the media info
+fn youtube_id(url: &str) -> Option<&str> {
+    let (_, rest) = url.split_once("://").unwrap_or(("", url));
+    let (host, path) = rest.split_once('/')?;
+    let host = host.trim_start_matches("www.").trim_start_matches("m.");
+
+    let id = match host {
+        "youtu.be" => path.split(['?', '#']).next()?,
+        "youtube.com" | "music.youtube.com" => {
+            if let Some(short) = path.strip_prefix("shorts/") {
+                short.split(['?', '#', '/']).next()?
+            } else {
+                let query = path.strip_prefix("watch?")?;
+                query
+                    .split('&')
+                    .find_map(|param| param.strip_prefix("v="))?
+            }
+        }
+        _ => return None,
+    };
+
+    (id.len() == 11).then_some(id)
+}
+
+/// Whether the input looks like an URL rather than a search term
+///
+/// A yt-dlp search (`ytsearch:foo`) is passed as is, and an URL without a scheme needs a path
+/// (`youtube.com/watch?v=...`), a dotted word like `some.song` being searched.
+fn looks_like_url(input: &str) -> bool {
+    if input
+        .split_once(':')
+        .is_some_and(|(engine, _)| parse::search_engine(engine).is_ok())
+    {
+        return true;
+    }
+    if input.chars().any(char::is_whitespace) {
+        return false;
+    }
+
+    input.contains("://")
+        || input.split_once('/').is_some_and(|(host, _)| {
+            host.contains('.') && !host.starts_with('.') && !host.ends_with('.')
+        })
+}
+
+/// Whether the yt-dlp errors are transient network errors, worth retrying
+///
+/// Only the connection, timeout, rate limit (429) and server (5xx) errors are, not the fatal
+/// ones like `Unable to download webpage: HTTP Error 404`.
+fn is_network_error(stderr: &str) -> bool {
+    const NETWORK_ERRORS: &[&str] = &[
+        "timed out",
+        "Connection reset",
+        "Connection refused",
+        "Connection aborted",
+        "Network is unreachable",
+        "Temporary failure in name resolution",
+        "IncompleteRead",
+        "HTTP Error 429",
+        "HTTP Error 5",
+    ];
+
+    stderr
+        .lines()
+        .filter(|line| line.starts_with("ERROR:"))
+        .any(|line| NETWORK_ERRORS.iter().any(|error| line.contains(error)))
+}
+
+/// Run the yt-dlp command, retrying with an exponential backoff when it fails on a network error
+///
+/// The error output is captured to recognize those errors, and then forwarded. With a spinner
+/// message, the standard output is also captured and forwarded once the spinner is cleared.
+/// With `stdout_to_stderr`, the standard output is forwarded to the error output instead.
+fn status_with_retries(
+    command: &mut Command,
+    retries: u32,
+    verbose: bool,
+    spinner: Option<&'static str>,
+    stdout_to_stderr: bool,
+) -> Result<ExitStatus, anyhow::Error> {
+    let spinner = spinner.map(|message| {
+        let spinner = ProgressBar::new_spinner().with_message(message);
+        spinner.enable_steady_tick(Duration::from_millis(100));
+        spinner
+    });
+    let print = |f: &mut dyn FnMut()| match &spinner {
+        Some(spinner) => spinner.suspend(f),
+        None => f(),
+    };
+
+    command
+        .stdout(if spinner.is_some() {
+            Stdio::piped()
+        } else if stdout_to_stderr {
+            std::io::stderr().into()
+        } else {
+            Stdio::inherit()
+        })
+        .stderr(Stdio::piped());
+
+    let mut attempt = 0;
+    let status = loop {
+        let output = command.output()?;
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        print(&mut || {
+            let _ = if stdout_to_stderr {
+                std::io::stderr().write_all(&output.stdout)
+            } else {
+                std::io::stdout().write_all(&output.stdout)
+            };
+            eprint!("{stderr}");
+        });
+
+        if output.status.success() || !is_network_error(&stderr) || attempt >= retries {
+            break output.status;
+        }
+
+        attempt += 1;
+        let backoff = Duration::from_secs(1 << (attempt - 1).min(6));
+        if verbose {
+            print(&mut || {
+                eprintln!(
+                    " -> retrying after a network error in {}s ({attempt}/{retries})",
+                    backoff.as_secs()
+                )
+            });
+        }
+        std::thread::sleep(backoff);
+    };
+
+    if let Some(spinner) = spinner {
+        spinner.finish_and_clear();
+    }
+    Ok(status)
+}
+
+/// Check that yt-dlp can be executed, with installation hints if not found
+fn check_yt_dlp(args: &Args) -> Result<(), anyhow::Error> {
+    let output = match Command::new(&args.yt_dlp).arg("--version").output() {
+        Ok(output) => output,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => bail!(
+            "yt-dlp not found at `{}`, install it (see https://github.com/yt-dlp/yt-dlp#installation) \
+             or pass its path with --yt-dlp",
+            args.yt_dlp.display()
+        ),
+        Err(err) => {
+            return Err(err)
+                .with_context(|| format!("unable to execute yt-dlp: {}", args.yt_dlp.display()))
+        }
+    };
+
+    if args.verbose > 0 {
+        eprintln!(
+            " -> yt-dlp version: {}",
+            String::from_utf8_lossy(&output.stdout).trim()
+        );
+    }
+
+    Ok(())
+}
+
+/// Search the term with the engine and let the user select one of the results
+///
+/// Returns the URL of the selected result, or `None` when cancelled.
+fn search(
+    args: &Args,
+    engine: &str,
+    cookies_from_browser: Option<&str>,
+    query: &str,
+) -> Result<Option<String>, anyhow::Error> {
+    let engine = if engine.ends_with(|c: char| c.is_ascii_digit()) {
+        Cow::Borrowed(engine)
+    } else {
+        Cow::Owned(format!("{engine}5"))
     };
 
-    let mut command = Command::new("yt-dlp");
+    let mut command = Command::new(&args.yt_dlp);
+
+    add_cookies(&mut command, args, cookies_from_browser);
+
+    command
+        .arg("--flat-playlist")
+        .arg("--dump-single-json")
+        .arg(format!("{engine}:{query}"));
+
+    if let Some(hook) = &args.command_hook {
+        run_command_hook(hook, &mut command)?;
+    }
+    command.stderr(Stdio::inherit());
+
+    if args.verbose > 0 {
+        eprintln!(" -> executing: {:?}", command);
+    }
+
+    let output = command.output()?;
 
-    if args.quiet {
-        command.arg("--quiet");
+    if !output.status.success() {
+        bail!("yt-dlp error: {:?}", command);
     }
 
-    if args.dirs {
-        let output = if matches!(preset, Preset::BestAudio) {
-            dirs::audio_dir().context("cloudn't get the audio directory")?
-        } else {
-            dirs::video_dir().context("couldn't get the video directory")?
-        };
+    let playlist: infojson::Playlist =
+        serde_json::from_slice(&output.stdout).context("unable to read the search results")?;
 
-        command.arg("-P").arg(output);
+    if playlist.entries.is_empty() {
+        bail!("no search results for: {query}");
     }
 
-    if matches!(preset, Preset::BestAudio) {
-        command.arg("-x");
+    if args.non_interactive {
+        let first = &playlist.entries[0];
+        eprintln!(
+            " -> note: using the first search result: {}",
+            first.title.as_deref().unwrap_or(&first.id)
+        );
+        return Ok(Some(first.url.clone()));
     }
 
-    if embed_thumbnail {
-        command.arg("--embed-thumbnail");
-    } else {
-        command.arg("--no-embed-thumbnail");
+    match prep_select_search_result(playlist.entries.iter()).prompt() {
+        Ok(SearchResultDisplay(entry)) => Ok(Some(entry.url.clone())),
+        Err(_) => Ok(None),
     }
+}
 
-    if embed_chapters {
-        command.arg("--embed-chapters");
-    } else {
-        command.arg("--no-embed-chapters");
-    }
+/// Let the `--command-hook` script rewrite the arguments of the command
+///
+/// Only the program and the arguments are kept, so it runs before the stdio are configured.
+fn run_command_hook(hook: &Path, command: &mut Command) -> Result<(), anyhow::Error> {
+    let output = Command::new(hook)
+        .args(command.get_args())
+        .stderr(Stdio::inherit())
+        .output()
+        .with_context(|| format!("unable to run the command hook: {}", hook.display()))?;
 
-    if sponsorblock_remove {
-        command.arg("--sponsorblock-remove=default");
-    } else {
-        command.arg("--no-sponsorblock");
+    if !output.status.success() {
+        bail!("command hook error: {}", hook.display());
     }
 
-    if let Some(embed_subs) = embed_subtitles {
-        command.arg("--embed-subs");
-        for sublang in embed_subs {
-            command.arg("--sub-lang");
-            command.arg(sublang.0);
+    let args = String::from_utf8(output.stdout)
+        .with_context(|| format!("invalid output from the command hook: {}", hook.display()))?;
+
+    let mut rewritten = Command::new(command.get_program());
+    rewritten.args(args.lines());
+    *command = rewritten;
+
+    Ok(())
+}
+
+/// Rewrite the info.json `thumbnails` list from the top-level `thumbnail`
+///
+/// yt-dlp only embeds from the `thumbnails` list, which some extractors don't provide.
+fn patch_thumbnails(path: &Path, thumbnail: &str) -> Result<(), anyhow::Error> {
+    let file =
+        File::open(path).with_context(|| format!("unable to open file: {}", path.display()))?;
+    let mut info_json: serde_json::Value = serde_json::from_reader(BufReader::new(file))
+        .with_context(|| format!("unable to read the info_json file: {}", path.display()))?;
+
+    info_json["thumbnails"] = serde_json::json!([{ "url": thumbnail, "id": "0" }]);
+
+    let file =
+        File::create(path).with_context(|| format!("unable to create file: {}", path.display()))?;
+    serde_json::to_writer(file, &info_json)
+        .with_context(|| format!("unable to write the info_json file: {}", path.display()))?;
+
+    Ok(())
+}
+
+/// Print rows as a table with each column padded to the same width
+fn print_table(header: &[&str], rows: &[Vec<String>]) {
+    let mut widths: Vec<usize> = header.iter().map(|h| h.chars().count()).collect();
+    for row in rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
         }
     }
 
-    command
-        .arg("--load-info-json")
-        .arg(info_json_entry.path())
-        .arg("--no-playlist")
-        .arg("-o")
-        .arg(output_template)
-        .arg("-f")
-        .arg({
-            let mut ff = String::new();
+    let print_row = |cells: &mut dyn Iterator<Item = &str>| {
+        let line: Vec<String> = cells
+            .zip(&widths)
+            .map(|(cell, width)| format!("{cell:<width$}"))
+            .collect();
+        println!("{}", line.join("  ").trim_end());
+    };
 
-            ff.push_str(&formats[0]);
-            for f in &formats[1..] {
-                ff.push_str("+");
-                ff.push_str(f);
-            }
+    print_row(&mut header.iter().copied());
+    for row in rows {
+        print_row(&mut row.iter().map(String::as_str));
+    }
+}
 
-            ff
+/// Print all the formats, best first
+fn print_formats(formats: &[infojson::Format]) {
+    let mut formats: Vec<&infojson::Format> = formats.iter().collect();
+    formats.sort_by(|a, b| {
+        b.vcodec
+            .is_some()
+            .cmp(&a.vcodec.is_some())
+            .then(b.height.cmp(&a.height))
+            .then(b.fps.unwrap_or(0.0).total_cmp(&a.fps.unwrap_or(0.0)))
+            .then(b.tbr.unwrap_or(0.0).total_cmp(&a.tbr.unwrap_or(0.0)))
+    });
+
+    let rows: Vec<Vec<String>> = formats
+        .iter()
+        .map(|f| {
+            let size = match (f.filesize, f.size()) {
+                (Some(size), _) => SizeFormatter::new(size, BINARY).to_string(),
+                (None, Some(size)) => format!("~{}", SizeFormatter::new(size, BINARY)),
+                (None, None) => String::new(),
+            };
+            vec![
+                f.format_id.clone(),
+                f.resolution.clone().unwrap_or_default(),
+                f.fps.map(|fps| format!("{fps}")).unwrap_or_default(),
+                f.vcodec.clone().unwrap_or_default(),
+                f.acodec.clone().unwrap_or_default(),
+                size,
+                f.protocol.clone(),
+            ]
         })
-        .args(&args.extras);
+        .collect();
 
-    if args.verbose > 0 {
-        println!(" -> executing: {:?}", command);
-    }
+    print_table(
+        &[
+            "ID",
+            "RESOLUTION",
+            "FPS",
+            "VCODEC",
+            "ACODEC",
+            "SIZE",
+            "PROTOCOL",
+        ],
+        &rows,
+    );
+}
 
-    let status = command.status()?;
+/// Print a side-by-side comparison of the top video formats
+fn print_format_comparison(formats: &[infojson::Format]) {
+    const TOP: usize = 5;
 
-    if !status.success() {
-        bail!("yt-dlp error: {:?}", command);
+    let mut formats: Vec<&infojson::Format> =
+        formats.iter().filter(|f| f.vcodec.is_some()).collect();
+    formats.sort_unstable_by(|a, b| {
+        b.height
+            .cmp(&a.height)
+            .then(b.fps.unwrap_or(0.0).total_cmp(&a.fps.unwrap_or(0.0)))
+            .then(b.tbr.unwrap_or(0.0).total_cmp(&a.tbr.unwrap_or(0.0)))
+    });
+
+    let rows: Vec<Vec<String>> = formats
+        .iter()
+        .take(TOP)
+        .map(|f| {
+            vec![
+                f.format_id.clone(),
+                f.resolution.clone().unwrap_or_default(),
+                f.vcodec.clone().unwrap_or_default(),
+                f.fps.map(|fps| format!("{fps}")).unwrap_or_default(),
+                f.filesize
+                    .map(|size| SizeFormatter::new(size, BINARY).to_string())
+                    .unwrap_or_default(),
+                f.tbr.map(|tbr| format!("{tbr:.0}k")).unwrap_or_default(),
+            ]
+        })
+        .collect();
+
+    print_table(
+        &["ID", "RESOLUTION", "CODEC", "FPS", "SIZE", "BITRATE"],
+        &rows,
+    );
+}
+
+/// Labeled summary of the media, one field per line
+struct MediaSummary<'a> {
+    info_json: &'a infojson::InfoJson,
+    /// Include the categories and tags
+    verbose: bool,
+}
+
+impl Display for MediaSummary<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let info_json = self.info_json;
+        let uploader = info_json.uploader.as_ref().or(info_json.channel.as_ref());
+        let views = info_json.view_count.map(|views| views.to_string());
+        let duration = info_json.duration_string.clone().or_else(|| {
+            let duration = info_json.duration? as u64;
+            Some(match duration / 3600 {
+                0 => format!("{}:{:02}", duration / 60, duration % 60),
+                hours => format!("{hours}:{:02}:{:02}", duration / 60 % 60, duration % 60),
+            })
+        });
+        let released = release_date(info_json);
+        let status = if info_json.is_live == Some(true) {
+            Some("live")
+        } else if info_json.was_live == Some(true) {
+            Some("was live")
+        } else {
+            None
+        };
+
+        let mut fields = vec![
+            ("Title", Some(info_json.title.as_str())),
+            ("Uploader", uploader.map(String::as_str)),
+            ("Duration", duration.as_deref()),
+            ("Views", views.as_deref()),
+            ("Released", released.as_deref()),
+            ("Status", status),
+        ];
+
+        let age_limit = info_json
+            .age_limit
+            .filter(|&age| age > 0)
+            .map(|age| format!("{age}+"));
+        fields.push(("Age limit", age_limit.as_deref()));
+
+        if let Some(description) = info_json
+            .description
+            .as_deref()
+            .filter(|d| is_short_description(d))
+        {
+            fields.push(("About", Some(description.trim())));
+        }
+
+        let categories = info_json.categories.as_ref().map(|c| c.join(", "));
+        let tags = info_json.tags.as_ref().map(|t| t.join(", "));
+        if self.verbose {
+            fields.push(("Categories", categories.as_deref()));
+            fields.push(("Tags", tags.as_deref()));
+        }
+
+        for (label, value) in fields {
+            if let Some(value) = value.filter(|v| !v.is_empty()) {
+                writeln!(f, " {:<12}{value}", format!("{label}:"))?;
+            }
+        }
+        Ok(())
     }
+}
 
-    drop(std::mem::ManuallyDrop::into_inner(tempdir));
-    Ok(())
+/// Write the protocol suffix, colored by how it downloads when `color` is set
+///
+/// Green for plain http(s), yellow for the fragmented HLS (m3u8) and cyan for DASH.
+fn write_protocol(
+    f: &mut std::fmt::Formatter<'_>,
+    protocol: &str,
+    color: bool,
+) -> std::fmt::Result {
+    let code = if protocol.contains("m3u8") {
+        Some(33)
+    } else if protocol.contains("dash") {
+        Some(36)
+    } else if protocol.split('+').all(|p| p == "https" || p == "http") {
+        Some(32)
+    } else {
+        None
+    };
+
+    match code.filter(|_| color) {
+        Some(code) => write!(f, " (\x1b[{code}m{protocol}\x1b[0m)"),
+        None => write!(f, " ({protocol})"),
+    }
 }
 
-struct AudioFormatDisplay<'a>(&'a infojson::Format);
+/// Audio format, with whether to color it
+struct AudioFormatDisplay<'a>(&'a infojson::Format, bool);
 
 impl Display for AudioFormatDisplay<'_> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         if let Some(acodec) = &self.0.acodec {
             write!(f, "{:4.4}", acodec)?;
         }
-        if let Some(asr) = self.0.asr {
-            f.write_str(" ")?; // todo
-            write!(f, "{}k", asr / 1000)?;
+        // numeric columns are right-aligned, and blank when unknown, to line up the options
+        match self.0.asr {
+            Some(asr) => write!(f, " {:>3}k", asr / 1000)?,
+            None => f.write_str("     ")?,
         }
-        if let Some(filesize) = self.0.filesize {
-            f.write_str(" ")?; // todo
-            write!(f, "{}", SizeFormatter::new(filesize, BINARY))?;
+        match self.0.abr {
+            Some(abr) => write!(f, " {:>4.0}k", abr)?,
+            None => f.write_str("      ")?,
+        }
+        match self.0.filesize {
+            Some(filesize) => write!(
+                f,
+                " {:>10}",
+                SizeFormatter::new(filesize, BINARY).to_string()
+            )?,
+            None => f.write_str("           ")?,
         }
         if let Some(format_note) = &self.0.format_note {
             f.write_str(" ")?; // todo
-            f.write_str(&format_note)?;
+            f.write_str(format_note)?;
         }
-        if let Some(protocol) = Some(&self.0.protocol) {
-            f.write_str(" (")?; // todo
-            f.write_str(&protocol)?;
-            f.write_str(")")?; // todo
-        }
-        Ok(())
+        write_protocol(f, &self.0.protocol, self.1)
     }
 }
 
 fn prep_select_audio<'a, I: Iterator<Item = &'a infojson::Format>>(
     formats: I,
+    color: bool,
 ) -> Select<'a, AudioFormatDisplay<'a>> {
-    let mut options: Vec<AudioFormatDisplay> = formats
-        .filter(|f| f.acodec.is_some() /*&& f.vcodec.is_none()*/)
-        .map(AudioFormatDisplay)
-        .collect();
+    let mut options: Vec<AudioFormatDisplay> =
+        formats.map(|f| AudioFormatDisplay(f, color)).collect();
 
-    options.sort_unstable_by_key(|f| Reverse(&f.0.asr));
+    options.sort_unstable_by(|a, b| {
+        let abr = |f: &AudioFormatDisplay| f.0.abr.unwrap_or_default();
+        b.0.asr.cmp(&a.0.asr).then(abr(b).total_cmp(&abr(a)))
+    });
 
-    Select::new("Which audio format do you want?", options).with_formatter(&|f| {
-        let mut buf = String::new();
+    Select::new("Which audio format do you want?", options)
+        .with_filter(&|input, f, _, _| matches_format(input, f.0))
+        .with_formatter(&|f| format!("{} - {}", f.value.0.format_id, f.value.0.quality_label()))
+}
 
-        buf.push_str(&f.value.0.format_id);
-        if let Some(acodec) = &f.value.0.acodec {
-            buf.push_str(" - ");
-            buf.push_str(acodec);
-        }
+/// Whether every word of the selector input is found in the format id, codecs or resolution
+fn matches_format(input: &str, format: &infojson::Format) -> bool {
+    let haystack = [
+        Some(format.format_id.as_str()),
+        format.vcodec.as_deref(),
+        format.acodec.as_deref(),
+        format.resolution.as_deref(),
+        format.format_note.as_deref(),
+        Some(&format.quality_label()),
+    ]
+    .into_iter()
+    .flatten()
+    .collect::<Vec<_>>()
+    .join(" ")
+    .to_lowercase();
 
-        buf
-    })
+    input
+        .to_lowercase()
+        .split_whitespace()
+        .all(|word| haystack.contains(word))
 }
 
-struct VideoFormatDisplay<'a>(&'a infojson::Format);
+/// Video format, with whether to color it
+struct VideoFormatDisplay<'a>(&'a infojson::Format, bool);
 
 impl Display for VideoFormatDisplay<'_> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
@@ -398,44 +3570,105 @@ impl Display for VideoFormatDisplay<'_> {
         if let Some(resolution) = &self.0.resolution {
             write!(f, " {}", resolution)?;
         }
+        if let Some(fps) = self.0.fps {
+            write!(f, " {fps:.0}fps")?;
+        }
+        if let Some(dynamic_range) = self.0.dynamic_range.as_ref().filter(|d| *d != "SDR") {
+            write!(f, " {dynamic_range}")?;
+        }
         if let Some(filesize) = self.0.filesize {
             f.write_str(" ")?; // todo
             write!(f, "{}", SizeFormatter::new(filesize, BINARY))?;
         }
         if let Some(format_note) = &self.0.format_note {
             f.write_str(" ")?; // todo
-            f.write_str(&format_note)?;
-        }
-        if let Some(protocol) = Some(&self.0.protocol) {
-            f.write_str(" (")?; // todo
-            f.write_str(&protocol)?;
-            f.write_str(")")?; // todo
+            f.write_str(format_note)?;
         }
-        Ok(())
+        write_protocol(f, &self.0.protocol, self.1)
     }
 }
 
 fn prep_select_video<'a, I: Iterator<Item = &'a infojson::Format>>(
     formats: I,
+    default_height: Option<i64>,
+    color: bool,
 ) -> Select<'a, VideoFormatDisplay<'a>> {
-    let mut options: Vec<VideoFormatDisplay> = formats
-        .filter(|f| f.vcodec.is_some() /*&& f.acodec.is_none()*/)
-        .map(VideoFormatDisplay)
-        .collect();
+    let mut options: Vec<VideoFormatDisplay> =
+        formats.map(|f| VideoFormatDisplay(f, color)).collect();
+
+    // HDR first among the formats of the same width
+    options.sort_unstable_by_key(|f| {
+        let is_hdr = f.0.dynamic_range.as_ref().is_some_and(|d| d != "SDR");
+        Reverse((f.0.width, is_hdr))
+    });
+
+    let starting_cursor = default_height
+        .and_then(|height| {
+            options
+                .iter()
+                .position(|f| f.0.height.is_some_and(|h| h <= height))
+        })
+        .unwrap_or(0);
+
+    Select::new("Which video format do you want?", options)
+        .with_starting_cursor(starting_cursor)
+        .with_filter(&|input, f, _, _| matches_format(input, f.0))
+        .with_formatter(&|f| format!("{} - {}", f.value.0.format_id, f.value.0.quality_label()))
+}
+
+struct SearchResultDisplay<'a>(&'a infojson::PlaylistEntry);
+
+impl Display for SearchResultDisplay<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.0.title.as_deref().unwrap_or(&self.0.id))?;
+        if let Some(channel) = self.0.channel.as_ref().or(self.0.uploader.as_ref()) {
+            write!(f, " - {channel}")?;
+        }
+        if let Some(duration) = self.0.duration {
+            let duration = duration as u64;
+            write!(f, " ({}:{:02})", duration / 60, duration % 60)?;
+        }
+        Ok(())
+    }
+}
 
-    options.sort_unstable_by_key(|f| Reverse(&f.0.width));
+fn prep_select_search_result<'a, I: Iterator<Item = &'a infojson::PlaylistEntry>>(
+    entries: I,
+) -> Select<'a, SearchResultDisplay<'a>> {
+    let entries = entries.map(SearchResultDisplay).collect();
+    Select::new("Which result do you want?", entries)
+}
 
-    Select::new("Which video format do you want?", options).with_formatter(&|f| {
-        let mut buf = String::new();
+struct ThumbnailDisplay<'a>(&'a infojson::Thumbnail);
 
-        buf.push_str(&f.value.0.format_id);
-        if let Some(vcodec) = &f.value.0.vcodec {
-            buf.push_str(" - ");
-            buf.push_str(vcodec);
+impl Display for ThumbnailDisplay<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match (&self.0.resolution, self.0.width, self.0.height) {
+            (Some(resolution), _, _) => write!(f, "{resolution:>9}")?,
+            (None, Some(width), Some(height)) => write!(f, "{:>9}", format!("{width}x{height}"))?,
+            _ => write!(f, "{:>9}", "unknown")?,
         }
+        if let Some(preference) = self.0.preference {
+            write!(f, " (preference {preference})")?;
+        }
+        write!(f, " {}", self.0.id)
+    }
+}
 
-        buf
-    })
+fn prep_select_thumbnail<'a, I: Iterator<Item = &'a infojson::Thumbnail>>(
+    thumbnails: I,
+) -> Select<'a, ThumbnailDisplay<'a>> {
+    let mut options: Vec<ThumbnailDisplay> = thumbnails.map(ThumbnailDisplay).collect();
+
+    let area = |t: &ThumbnailDisplay| t.0.width.unwrap_or(0) * t.0.height.unwrap_or(0);
+    options.sort_by(|a, b| {
+        b.0.preference
+            .cmp(&a.0.preference)
+            .then(area(b).cmp(&area(a)))
+    });
+
+    Select::new("Which thumbnail do you want to embed?", options)
+        .with_formatter(&|t| t.value.to_string().trim_start().to_string())
 }
 
 struct PresetDisplay(Preset);
@@ -457,25 +3690,614 @@ fn prep_select_preset<'a, I: Iterator<Item = Preset>>(presets: I) -> Select<'a,
     Select::new("Which preset do you want to use?", presets)
 }
 
-struct SubtitleDisplay<'a>(&'a str, &'a [infojson::SubtitleInfo]);
+struct SubtitleDisplay<'a> {
+    lang: &'a str,
+    name: Option<&'a str>,
+    /// Automatic captions rather than subtitles
+    auto: bool,
+}
 
 impl Display for SubtitleDisplay<'_> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(
-            f,
-            "{}",
-            self.1
-                .get(0)
-                .map(|info| info.name.as_deref())
-                .flatten()
-                .unwrap_or(self.0)
-        )
+        f.write_str(self.name.unwrap_or(self.lang))?;
+        if self.auto {
+            f.write_str(" (auto-generated)")?;
+        }
+        Ok(())
+    }
+}
+
+/// Subtitles matching the languages, like `en` or `en.*` for all the English variants
+///
+/// Warns about the languages without any matching subtitles.
+fn match_sub_langs<'a>(
+    langs: &[String],
+    mut subs: Vec<SubtitleDisplay<'a>>,
+) -> Vec<SubtitleDisplay<'a>> {
+    subs.sort_unstable_by_key(|sub| (sub.lang, sub.auto));
+
+    let matches = |lang: &str, code: &str| match lang.strip_suffix(".*") {
+        Some(prefix) => code.starts_with(prefix),
+        None => lang == "all" || lang == code,
+    };
+
+    for lang in langs {
+        if !subs.iter().any(|sub| matches(lang, sub.lang)) {
+            eprintln!(" -> warning: no subtitles matching `{lang}`");
+        }
     }
+
+    subs.retain(|sub| langs.iter().any(|lang| matches(lang, sub.lang)));
+    // prefer the subtitles over the automatic captions of the same language
+    subs.dedup_by_key(|sub| sub.lang);
+    subs
 }
 
-fn prep_multiselect_subtitle<'a, I: Iterator<Item = (&'a str, &'a [infojson::SubtitleInfo])>>(
-    subs: I,
+fn prep_multiselect_subtitle<'a>(
+    mut subs: Vec<SubtitleDisplay<'a>>,
 ) -> MultiSelect<'a, SubtitleDisplay<'a>> {
-    let subs = subs.map(|(a, b)| SubtitleDisplay(a, b)).collect();
+    // HashMap iteration order is random, sort by display name for a stable list
+    subs.sort_by_cached_key(|sub| (sub.auto, sub.to_string(), sub.lang));
+
     MultiSelect::new("Do you want to embed a subtitle?", subs)
 }
+
+/// Add the `--cookies` and `--cookies-from-browser` arguments to a yt-dlp command
+/// Key of the cached info json: the url and the arguments changing the fetched metadata
+fn cache_key<'a>(
+    args: &'a Args,
+    cookies_from_browser: Option<&'a str>,
+    url: &'a str,
+) -> Vec<&'a OsStr> {
+    let mut key = vec![OsStr::new(url)];
+
+    if !args.playlist {
+        key.push(OsStr::new("--no-playlist"));
+    }
+    if let Some(cookies) = &args.cookies {
+        key.extend([OsStr::new("--cookies"), cookies.as_os_str()]);
+    }
+    if let Some(cookies_from_browser) = cookies_from_browser {
+        key.extend([
+            OsStr::new("--cookies-from-browser"),
+            OsStr::new(cookies_from_browser),
+        ]);
+    }
+    if let Some(hook) = &args.command_hook {
+        key.extend([OsStr::new("--command-hook"), hook.as_os_str()]);
+    }
+    key.extend(args.extras.iter().map(OsStr::new));
+
+    key
+}
+
+fn add_cookies(command: &mut Command, args: &Args, cookies_from_browser: Option<&str>) {
+    if let Some(cookies) = &args.cookies {
+        command.arg("--cookies").arg(cookies);
+    }
+
+    if let Some(cookies_from_browser) = cookies_from_browser {
+        command
+            .arg("--cookies-from-browser")
+            .arg(cookies_from_browser);
+    }
+}
+
+/// Resolve the profile of a `--cookies-from-browser` spec
+///
+/// When no profile is given and the browser has multiple ones, let the user select one.
+fn select_browser_profile(spec: &str) -> Result<String, inquire::InquireError> {
+    let (head, container) = match spec.split_once("::") {
+        Some((head, container)) => (head, Some(container)),
+        None => (spec, None),
+    };
+
+    if head.contains(':') {
+        return Ok(spec.to_string());
+    }
+
+    let browser = head.split('+').next().unwrap_or(head);
+    let profiles = browser::profiles(&browser.to_ascii_lowercase());
+    if profiles.len() <= 1 {
+        return Ok(spec.to_string());
+    }
+
+    let profile = Select::new("Which browser profile do you want to use?", profiles).prompt()?;
+
+    Ok(match container {
+        Some(container) => format!("{head}:{profile}::{container}"),
+        None => format!("{head}:{profile}"),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn sanitize_title_replaces_path_separators() {
+        assert_eq!(
+            sanitize_title("AC/DC - Live\\Remix", None),
+            "AC⧸DC - Live⧹Remix"
+        );
+    }
+
+    #[test]
+    fn sanitize_title_keeps_colons_and_emoji() {
+        assert_eq!(
+            sanitize_title("Part 1: The Start 🎉🚀", None),
+            "Part 1: The Start 🎉🚀"
+        );
+    }
+
+    #[test]
+    fn sanitize_title_removes_control_characters() {
+        assert_eq!(sanitize_title("Line\none\tTab", None), "LineoneTab");
+    }
+
+    #[test]
+    fn sanitize_title_escapes_percent() {
+        assert_eq!(sanitize_title("100% %(id)s", None), "100%% %%(id)s");
+    }
+
+    #[test]
+    fn sanitize_title_replacements_override_the_defaults() {
+        let replacements = BTreeMap::from([
+            ("/".to_string(), "-".to_string()),
+            (":".to_string(), " -".to_string()),
+        ]);
+        assert_eq!(
+            sanitize_title("AC/DC: Live\\Remix", Some(&replacements)),
+            "AC-DC - Live⧹Remix"
+        );
+    }
+
+    #[test]
+    fn filter_formats_skips_blank_format_ids() {
+        let formats = [
+            infojson::tests::format(serde_json::json!({ "format_id": " ", "vcodec": "avc1" })),
+            infojson::tests::format(serde_json::json!({ "vcodec": "vp9" })),
+            infojson::tests::format(serde_json::json!({ "format_id": "137", "vcodec": "avc1" })),
+        ];
+        let filters = [FormatFilter::new("video codec", |f| f.vcodec.is_some())];
+
+        let kept = filter_formats("video", &formats, &filters, false)
+            .unwrap()
+            .unwrap();
+        let ids: Vec<&str> = kept.iter().map(|f| f.format_id.as_str()).collect();
+        assert_eq!(ids, ["137"]);
+    }
+
+    #[test]
+    fn format_spec_drops_blank_formats() {
+        let formats: Vec<Cow<str>> = vec!["137".into(), "".into(), " ".into(), "140".into()];
+        assert_eq!(format_spec(&formats), "137+140");
+        assert_eq!(format_spec(&["  ".into()]), "");
+    }
+
+    #[test]
+    fn subtitles_are_in_a_stable_order() {
+        let names = HashMap::from([
+            ("fr", Some("French")),
+            ("en", Some("English")),
+            ("de", None),
+            ("en-GB", Some("English (United Kingdom)")),
+        ]);
+        let subs = || {
+            let mut subs: Vec<SubtitleDisplay> = names
+                .iter()
+                .map(|(&lang, &name)| SubtitleDisplay {
+                    lang,
+                    name,
+                    auto: false,
+                })
+                .collect();
+            subs.push(SubtitleDisplay {
+                lang: "en",
+                name: Some("English"),
+                auto: true,
+            });
+            subs
+        };
+
+        let labels = |subs| -> Vec<String> {
+            prep_multiselect_subtitle(subs)
+                .options
+                .iter()
+                .map(|sub| sub.to_string())
+                .collect()
+        };
+        let expected = [
+            "English",
+            "English (United Kingdom)",
+            "French",
+            "de",
+            "English (auto-generated)",
+        ];
+        assert_eq!(labels(subs()), expected);
+
+        let mut reversed = subs();
+        reversed.reverse();
+        assert_eq!(labels(reversed), expected);
+    }
+
+    #[test]
+    fn merge_output_format_defaults_to_mkv_with_embedded_subtitles() {
+        let custom = |chosen, embed_subtitles| {
+            merge_output_format(Preset::Custom, chosen, embed_subtitles, true)
+        };
+
+        assert_eq!(custom(None, true), Some(Container::Mkv));
+        assert_eq!(custom(None, false), None);
+        assert_eq!(custom(Some(Container::Mp4), true), Some(Container::Mp4));
+        assert_eq!(custom(Some(Container::Webm), false), Some(Container::Webm));
+    }
+
+    #[test]
+    fn merge_output_format_only_when_merging() {
+        assert_eq!(
+            merge_output_format(Preset::Best, None, false, true),
+            Some(Container::Mkv)
+        );
+        assert_eq!(merge_output_format(Preset::Custom, None, true, false), None);
+        assert_eq!(
+            merge_output_format(Preset::BestVideo, Some(Container::Mp4), true, false),
+            None
+        );
+    }
+
+    fn kept_video_ids(args: &[&str], formats: &[infojson::Format]) -> Vec<String> {
+        let args = Args::parse_from(args);
+        let filters = video_filters(&args, formats);
+        filter_formats("video", formats, &filters, args.lenient)
+            .unwrap()
+            .unwrap()
+            .iter()
+            .map(|f| f.format_id.clone())
+            .collect()
+    }
+
+    #[test]
+    fn storyboards_are_excluded_by_default() {
+        let formats = [
+            infojson::tests::format(serde_json::json!({
+                "format_id": "sb0", "vcodec": "none", "acodec": "none",
+                "ext": "mhtml", "rows": 10, "columns": 10,
+            })),
+            infojson::tests::format(serde_json::json!({ "format_id": "137", "vcodec": "avc1" })),
+        ];
+
+        assert_eq!(kept_video_ids(&["md", "x"], &formats), ["137"]);
+        assert_eq!(
+            kept_video_ids(&["md", "--show-storyboards", "x"], &formats),
+            ["sb0", "137"]
+        );
+    }
+
+    #[test]
+    fn min_fps_excludes_unknown_framerates_unless_lenient() {
+        let formats = [
+            infojson::tests::format(
+                serde_json::json!({ "format_id": "30", "vcodec": "avc1", "fps": 30 }),
+            ),
+            infojson::tests::format(
+                serde_json::json!({ "format_id": "60", "vcodec": "avc1", "fps": 60 }),
+            ),
+            infojson::tests::format(
+                serde_json::json!({ "format_id": "unknown", "vcodec": "avc1" }),
+            ),
+        ];
+
+        assert_eq!(
+            kept_video_ids(&["md", "--min-fps", "60", "x"], &formats),
+            ["60"]
+        );
+        assert_eq!(
+            kept_video_ids(&["md", "--min-fps", "60", "--lenient", "x"], &formats),
+            ["60", "unknown"]
+        );
+    }
+
+    #[test]
+    fn thumbnails_are_patched_from_the_thumbnail() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("episode.info.json");
+        let mut video = infojson::tests::minimal_video();
+        video["thumbnail"] = "https://podcast.example.org/cover.jpg".into();
+        std::fs::write(&path, video.to_string()).unwrap();
+        assert!(infojson::tests::parse_video(video).thumbnails.is_none());
+
+        patch_thumbnails(&path, "https://podcast.example.org/cover.jpg").unwrap();
+
+        let patched: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        let info_json = infojson::tests::parse_video(patched);
+        let thumbnails = info_json.thumbnails.unwrap();
+        assert_eq!(thumbnails.len(), 1);
+        assert_eq!(thumbnails[0].url, "https://podcast.example.org/cover.jpg");
+        assert_eq!(info_json.title, "Episode 1");
+    }
+
+    #[test]
+    fn release_date_with_and_without_timestamps() {
+        let mut video = infojson::tests::minimal_video();
+        assert_eq!(
+            release_date(&infojson::tests::parse_video(video.clone())),
+            None
+        );
+
+        video["upload_date"] = "20230510".into();
+        assert_eq!(
+            release_date(&infojson::tests::parse_video(video.clone())).as_deref(),
+            Some("2023-05-10")
+        );
+
+        video["timestamp"] = 1683720000.into();
+        let local = DateTime::from_timestamp(1683720000, 0)
+            .unwrap()
+            .with_timezone(&Local)
+            .format("%Y-%m-%d %H:%M")
+            .to_string();
+        assert_eq!(
+            release_date(&infojson::tests::parse_video(video)),
+            Some(local)
+        );
+    }
+
+    #[test]
+    fn read_info_jsons_keeps_only_the_videos() {
+        let dir = TempDir::new().unwrap();
+        let mut second = infojson::tests::minimal_video();
+        second["id"] = "def456".into();
+        let playlist = serde_json::json!({ "_type": "playlist", "id": "PL1", "title": "Season 1" });
+        std::fs::write(
+            dir.path().join("1.info.json"),
+            infojson::tests::minimal_video().to_string(),
+        )
+        .unwrap();
+        std::fs::write(dir.path().join("2.info.json"), second.to_string()).unwrap();
+        std::fs::write(dir.path().join("PL1.info.json"), playlist.to_string()).unwrap();
+        std::fs::write(dir.path().join("1.jpg"), b"").unwrap();
+        std::fs::create_dir(dir.path().join("3.info.json")).unwrap();
+
+        let mut ids: Vec<String> = read_info_jsons(dir.path(), false)
+            .unwrap()
+            .into_iter()
+            .map(|(_, info_json)| info_json.id)
+            .collect();
+        ids.sort();
+        assert_eq!(ids, ["abc123", "def456"]);
+    }
+
+    #[test]
+    fn read_info_jsons_errors() {
+        let error = |dir: &TempDir| read_info_jsons(dir.path(), false).unwrap_err().to_string();
+
+        let empty = TempDir::new().unwrap();
+        assert_eq!(
+            error(&empty),
+            "directory empty, yt-dlp didn't write the info json"
+        );
+
+        let playlist = TempDir::new().unwrap();
+        std::fs::write(
+            playlist.path().join("PL1.info.json"),
+            serde_json::json!({ "_type": "playlist", "id": "PL1" }).to_string(),
+        )
+        .unwrap();
+        assert_eq!(error(&playlist), "the playlist has no videos");
+
+        let no_info_json = TempDir::new().unwrap();
+        std::fs::write(no_info_json.path().join("1.jpg"), b"").unwrap();
+        assert!(error(&no_info_json).starts_with("no .info.json file among the 1 file(s)"));
+    }
+
+    #[test]
+    fn overwrite_choice_applies_to_the_download_and_post_processed_files() {
+        assert_eq!(
+            overwrite_args(false, false, &[]),
+            ["--no-post-overwrites", "--no-overwrites"]
+        );
+        assert_eq!(overwrite_args(true, false, &[]), ["--force-overwrites"]);
+        assert_eq!(
+            overwrite_args(true, true, &[]),
+            ["--no-post-overwrites", "--force-overwrites"]
+        );
+    }
+
+    #[test]
+    fn overwrite_flags_in_extras_take_precedence() {
+        let extras = |extras: &[&str]| -> Vec<String> {
+            extras.iter().map(|extra| extra.to_string()).collect()
+        };
+
+        assert_eq!(
+            overwrite_args(true, false, &extras(&["-w"])),
+            ["--no-post-overwrites"]
+        );
+        assert!(overwrite_args(false, false, &extras(&["--force-overwrites"])).is_empty());
+        assert!(overwrite_args(false, false, &extras(&["--no-force-overwrites"])).is_empty());
+        assert_eq!(
+            overwrite_args(false, true, &extras(&["--force-overwrites"])),
+            ["--no-post-overwrites"]
+        );
+    }
+
+    #[test]
+    fn embed_thumbnail_config_overrides_the_heuristic() {
+        assert!(embed_thumbnail_default(None, Preset::BestAudio, || true));
+        assert!(!embed_thumbnail_default(None, Preset::BestAudio, || false));
+        assert!(!embed_thumbnail_default(None, Preset::Best, || true));
+        assert!(!embed_thumbnail_default(
+            Some(false),
+            Preset::BestAudio,
+            || true
+        ));
+        assert!(embed_thumbnail_default(Some(true), Preset::Best, || false));
+    }
+
+    #[test]
+    fn config_dump_shows_the_layers() {
+        let mut config: config::Config = toml::from_str(
+            r#"
+            default_preset = "best-audio"
+            embed_chapters = false
+
+            [profile.phone]
+            default_preset = "best"
+            quality = 720
+            merge_output_format = "mp4"
+            "#,
+        )
+        .unwrap();
+        let matches = Args::command().get_matches_from([
+            "md",
+            "--profile",
+            "phone",
+            "--merge-output-format",
+            "webm",
+            "--config-dump",
+        ]);
+        let mut args = Args::from_arg_matches(&matches).unwrap();
+        let profile = config.apply_profile("phone").unwrap();
+        args.quality = args.quality.or(profile.quality);
+
+        let dump = config_dump(&args, &matches, &config, None, Some(&profile));
+        let lines: Vec<&str> = dump.lines().collect();
+        assert!(lines.contains(&r#"default_preset = "best" # profile phone"#));
+        assert!(lines.contains(&"embed_chapters = false # config file"));
+        assert!(lines.contains(&"# use_dirs is unset"));
+        assert!(lines.contains(&"quality = 720 # profile phone"));
+        assert!(lines.contains(&r#"merge_output_format = "webm" # --merge-output-format"#));
+        assert!(dump.parse::<toml::Table>().is_ok());
+    }
+
+    #[test]
+    fn sponsorblock_marks_embed_the_chapters() {
+        let (arg, warning) = embed_chapters_arg(false, Some(SponsorblockMode::Mark));
+        assert_eq!(arg, "--embed-chapters");
+        assert!(warning.is_some());
+
+        assert_eq!(
+            embed_chapters_arg(true, Some(SponsorblockMode::Mark)),
+            ("--embed-chapters", None)
+        );
+        assert_eq!(
+            embed_chapters_arg(false, Some(SponsorblockMode::Remove)),
+            ("--no-embed-chapters", None)
+        );
+        assert_eq!(
+            embed_chapters_arg(false, None),
+            ("--no-embed-chapters", None)
+        );
+    }
+
+    #[test]
+    fn directory_of_a_bare_file_name_is_the_current_one() {
+        assert_eq!(directory_of(Path::new("Title.mkv")), Path::new("."));
+        assert_eq!(
+            directory_of(Path::new("Videos/Title.mkv")),
+            Path::new("Videos")
+        );
+        assert_eq!(directory_of(Path::new("/Title.mkv")), Path::new("/"));
+    }
+
+    #[test]
+    fn reusable_command_downloads_from_the_url() {
+        let mut command = Command::new("yt-dlp");
+        command
+            .args([
+                "--print-to-file",
+                "after_move:filepath",
+                "/tmp/md/downloaded.json",
+            ])
+            .args(["--print-to-file", "title", "titles.txt"])
+            .args(["--load-info-json", "/tmp/md/Title [abc123].info.json"])
+            .args(["-f", "137+140"]);
+
+        let reusable = reusable_command(
+            &command,
+            Path::new("/tmp/md"),
+            "https://www.youtube.com/watch?v=abc123",
+        );
+        assert_eq!(
+            shell::command_line(&reusable),
+            "yt-dlp --print-to-file title titles.txt 'https://www.youtube.com/watch?v=abc123' -f 137+140"
+        );
+    }
+
+    #[test]
+    fn only_network_errors_are_retried() {
+        for error in [
+            "ERROR: [youtube] abc123: Unable to download webpage: HTTP Error 503: Service Unavailable",
+            "ERROR: [youtube] abc123: Unable to download API page: HTTP Error 429: Too Many Requests",
+            "ERROR: [generic] Unable to download webpage: <urlopen error [Errno 101] Network is unreachable>",
+            "ERROR: [youtube] abc123: Unable to download webpage: The read operation timed out",
+        ] {
+            assert!(is_network_error(error), "{error}");
+        }
+
+        for error in [
+            "ERROR: [generic] Unable to download webpage: HTTP Error 404: Not Found",
+            "ERROR: [vimeo] 123: Unable to download JSON metadata: HTTP Error 403: Forbidden",
+            "ERROR: [youtube] abc123: Video unavailable",
+            "WARNING: [youtube] Connection reset, retrying (1/3)\nERROR: [youtube] abc123: Private video",
+        ] {
+            assert!(!is_network_error(error), "{error}");
+        }
+    }
+
+    #[test]
+    fn cache_key_holds_the_fetch_arguments() {
+        let url = "https://www.youtube.com/watch?v=abc123";
+        let key = |cli: &[&str], cookies_from_browser| {
+            let args = Args::parse_from([&["md"], cli, &[url]].concat());
+            cache_key(&args, cookies_from_browser, url)
+                .iter()
+                .map(|part| part.to_string_lossy().into_owned())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(key(&[], None), [url, "--no-playlist"]);
+        assert_eq!(key(&["--playlist"], None), [url]);
+        assert_eq!(
+            key(
+                &["--cookies", "c.txt", "--command-hook", "hook.sh"],
+                Some("firefox")
+            ),
+            [
+                url,
+                "--no-playlist",
+                "--cookies",
+                "c.txt",
+                "--cookies-from-browser",
+                "firefox",
+                "--command-hook",
+                "hook.sh",
+            ]
+        );
+
+        let args = Args::parse_from(["md", url, "--", "--format-sort", "res"]);
+        assert_eq!(
+            cache_key(&args, None, url)[2..],
+            [OsStr::new("--format-sort"), OsStr::new("res")]
+        );
+    }
+
+    #[test]
+    fn urls_and_search_terms() {
+        assert!(looks_like_url("https://www.youtube.com/watch?v=abc123"));
+        assert!(looks_like_url("http://example.org"));
+        assert!(looks_like_url("youtube.com/watch?v=abc123"));
+        assert!(looks_like_url("youtu.be/abc123"));
+        assert!(looks_like_url("ytsearch:foo"));
+        assert!(looks_like_url("ytsearch10:lofi hip hop"));
+
+        assert!(!looks_like_url("lofi hip hop radio"));
+        assert!(!looks_like_url("some.song"));
+        assert!(!looks_like_url("mr. brightside"));
+        assert!(!looks_like_url("AC/DC"));
+        assert!(!looks_like_url("foo"));
+    }
+}
@@ -0,0 +1,483 @@
+//! CLI values parsers
+
+/// Parse a rate in bytes per second, like yt-dlp (`50K`, `4.2M`)
+pub fn rate(value: &str) -> Result<String, String> {
+    let number = value.trim_end_matches(|c: char| "kKmMgGtT".contains(c));
+
+    if value.len() - number.len() > 1 {
+        return Err(format!("invalid rate suffix in `{value}`"));
+    }
+    if number.is_empty() || !number.chars().all(|c| c.is_ascii_digit() || c == '.') {
+        return Err(format!(
+            "invalid rate `{value}`, expected e.g. `50K` or `4.2M`"
+        ));
+    }
+    number
+        .parse::<f64>()
+        .map_err(|_| format!("invalid rate `{value}`, expected e.g. `50K` or `4.2M`"))?;
+
+    Ok(value.to_string())
+}
+
+/// Parse a subtitle format preference, like yt-dlp (`srt`, `ass/srt/best`)
+pub fn sub_format(value: &str) -> Result<String, String> {
+    const FORMATS: &[&str] = &[
+        "srt", "vtt", "ass", "lrc", "ttml", "srv1", "srv2", "srv3", "json3", "best",
+    ];
+
+    for format in value.split('/') {
+        if !FORMATS.contains(&format) {
+            return Err(format!(
+                "unknown subtitle format `{format}`, expected one of: {}",
+                FORMATS.join(", ")
+            ));
+        }
+    }
+
+    Ok(value.to_string())
+}
+
+/// Parse a yt-dlp search prefix, without the colon (`ytsearch`, `ytsearch10`)
+pub fn search_engine(value: &str) -> Result<String, String> {
+    let name = value.trim_end_matches(|c: char| c.is_ascii_digit());
+
+    if !name.ends_with("search") || !name.chars().all(|c| c.is_ascii_alphanumeric()) {
+        return Err(format!(
+            "invalid search engine `{value}`, expected e.g. `ytsearch` or `ytsearch10`"
+        ));
+    }
+
+    Ok(value.to_string())
+}
+
+/// Parse a retry sleep spec, like yt-dlp (`3`, `linear=1::2`, `fragment:exp=1:20`)
+pub fn retry_sleep(value: &str) -> Result<String, String> {
+    const TYPES: &[&str] = &["http", "fragment", "file_access", "extractor"];
+
+    let expr = match value.split_once(':') {
+        Some((kind, expr)) if !kind.contains('=') && kind.parse::<f64>().is_err() => {
+            if !TYPES.contains(&kind) {
+                return Err(format!(
+                    "unknown retry type `{kind}`, expected one of: {}",
+                    TYPES.join(", ")
+                ));
+            }
+            expr
+        }
+        _ => value,
+    };
+
+    let is_number = |n: &str| n.is_empty() || n.parse::<f64>().is_ok();
+    let is_valid = match expr.split_once('=') {
+        Some(("linear" | "exp", params)) => params.split(':').all(is_number),
+        Some(_) => false,
+        None => !expr.is_empty() && is_number(expr),
+    };
+
+    if !is_valid {
+        return Err(format!(
+            "invalid retry sleep `{value}`, expected e.g. `3`, `linear=1::2` or `exp=1:20`"
+        ));
+    }
+
+    Ok(value.to_string())
+}
+
+/// Parse a format sort order, like yt-dlp `-S` (`res,fps`, `+size,vcodec:av01`)
+pub fn format_sort(value: &str) -> Result<String, String> {
+    const FIELDS: &[&str] = &[
+        "hasvid",
+        "hasaud",
+        "ie_pref",
+        "lang",
+        "quality",
+        "source",
+        "proto",
+        "vcodec",
+        "acodec",
+        "codec",
+        "vext",
+        "aext",
+        "ext",
+        "filesize",
+        "fs_approx",
+        "size",
+        "height",
+        "width",
+        "res",
+        "fps",
+        "hdr",
+        "channels",
+        "tbr",
+        "vbr",
+        "abr",
+        "br",
+        "asr",
+        "id",
+    ];
+
+    for token in value.split(',') {
+        let field = token
+            .trim_start_matches('+')
+            .split([':', '~'])
+            .next()
+            .unwrap_or_default();
+
+        if !FIELDS.contains(&field) {
+            return Err(format!(
+                "unknown format sort field `{field}` in `{token}`, expected one of: {}",
+                FIELDS.join(", ")
+            ));
+        }
+    }
+
+    Ok(value.to_string())
+}
+
+/// Parse a maximum video height (`720`, `1080`, `1440`, `2160`)
+pub fn quality(value: &str) -> Result<u32, String> {
+    const HEIGHTS: &[u32] = &[720, 1080, 1440, 2160];
+
+    match value.trim_end_matches('p').parse::<u32>() {
+        Ok(height) if HEIGHTS.contains(&height) => Ok(height),
+        _ => Err(format!(
+            "unsupported quality `{value}`, expected one of: 720, 1080, 1440, 2160"
+        )),
+    }
+}
+
+/// SponsorBlock categories that can be removed
+pub const SPONSORBLOCK_CATEGORIES: &[&str] = &[
+    "sponsor",
+    "intro",
+    "outro",
+    "selfpromo",
+    "interaction",
+    "music_offtopic",
+];
+
+/// Parse a list of SponsorBlock categories (`sponsor,intro`)
+pub fn sponsorblock(value: &str) -> Result<String, String> {
+    for category in value.split(',') {
+        if !SPONSORBLOCK_CATEGORIES.contains(&category) {
+            return Err(format!(
+                "unknown sponsorblock category `{category}`, expected one of: {}",
+                SPONSORBLOCK_CATEGORIES.join(", ")
+            ));
+        }
+    }
+
+    Ok(value.to_string())
+}
+
+/// Parse a size in bytes (`500MB`, `1.5GiB`, `50M`), a bare unit letter being binary like yt-dlp
+pub fn filesize(value: &str) -> Result<u64, String> {
+    let split = value
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+
+    let multiplier: u64 = match unit.to_ascii_lowercase().as_str() {
+        "" | "b" => 1,
+        "k" | "kib" => 1 << 10,
+        "m" | "mib" => 1 << 20,
+        "g" | "gib" => 1 << 30,
+        "t" | "tib" => 1 << 40,
+        "kb" => 1_000,
+        "mb" => 1_000_000,
+        "gb" => 1_000_000_000,
+        "tb" => 1_000_000_000_000,
+        _ => return Err(format!("invalid size unit in `{value}`")),
+    };
+
+    match number.parse::<f64>() {
+        Ok(number) if multiplier == 1 && number.fract() != 0.0 => Err(format!(
+            "invalid size `{value}`, a size in bytes can't have a fraction"
+        )),
+        Ok(number) if number.is_finite() => Ok((number * multiplier as f64).round() as u64),
+        _ => Err(format!(
+            "invalid size `{value}`, expected e.g. `500MB` or `1.5GiB`"
+        )),
+    }
+}
+
+/// Parse a yt-dlp output template (`%(uploader)s - %(title)s.%(ext)s`), with the title field
+pub fn title_template(value: &str) -> Result<String, String> {
+    const CONVERSIONS: &str = "diouxXeEfFgGcrsaBjlqDSUhp";
+
+    let mut rest = value;
+    while let Some(start) = rest.find(['%', '{', '}']) {
+        let (special, after) = rest[start..].split_at(1);
+        if special != "%" {
+            return Err(format!(
+                "unexpected `{special}` in `{value}`, the fields are written like `%(title)s`"
+            ));
+        }
+
+        if let Some(after) = after.strip_prefix('%') {
+            rest = after;
+            continue;
+        }
+        let Some(field) = after.strip_prefix('(') else {
+            return Err(format!(
+                "lone `%` in `{value}`, use `%%` for a literal `%` or `%(field)s` for a field"
+            ));
+        };
+
+        let unclosed = || format!("unclosed field in `{value}`, expected e.g. `%(title)s`");
+        let end = field.find(')').ok_or_else(unclosed)?;
+        let (name, after) = (&field[..end], &field[end + 1..]);
+        if name.contains("%(") {
+            return Err(unclosed());
+        }
+        if name.is_empty() {
+            return Err(format!("empty field name in `{value}`"));
+        }
+        if name.matches('{').count() != name.matches('}').count() {
+            return Err(format!(
+                "unbalanced braces in the field `{name}` of `{value}`"
+            ));
+        }
+
+        // flags, width and precision before the conversion type, like `%(id)05d`
+        let after = after.trim_start_matches(|c: char| "#0- +.".contains(c) || c.is_ascii_digit());
+        match after.chars().next() {
+            Some(conversion) if CONVERSIONS.contains(conversion) => rest = &after[1..],
+            _ => {
+                return Err(format!(
+                    "missing the conversion type after the field `{name}` in `{value}`, like `s` in `%({name})s`"
+                ))
+            }
+        }
+    }
+
+    if !value.contains("%(title)s") {
+        return Err(format!(
+            "missing the `%(title)s` field in `{value}`, holding the title from the prompt"
+        ));
+    }
+
+    Ok(value.to_string())
+}
+
+/// Parse an http(s) url with a host (`https://sponsor.ajay.app`)
+pub fn http_url(value: &str) -> Result<String, String> {
+    let invalid = || format!("invalid url `{value}`, expected e.g. `https://sponsor.ajay.app`");
+
+    let (scheme, rest) = value.split_once("://").ok_or_else(invalid)?;
+    if !scheme.eq_ignore_ascii_case("http") && !scheme.eq_ignore_ascii_case("https") {
+        return Err(format!(
+            "unsupported scheme `{scheme}` in `{value}`, expected http or https"
+        ));
+    }
+
+    let host = rest.split(['/', '?', '#']).next().unwrap_or_default();
+    let host = host.rsplit_once('@').map_or(host, |(_, host)| host);
+    if host.is_empty() || host.starts_with(':') || host.chars().any(char::is_whitespace) {
+        return Err(invalid());
+    }
+
+    Ok(value.to_string())
+}
+
+/// Parse a browser to load the cookies from, like yt-dlp (`firefox`, `chrome+gnomekeyring:Profile 1`)
+pub fn cookies_from_browser(value: &str) -> Result<String, String> {
+    const BROWSERS: &[&str] = &[
+        "brave", "chrome", "chromium", "edge", "firefox", "opera", "safari", "vivaldi", "whale",
+    ];
+    const KEYRINGS: &[&str] = &[
+        "basictext",
+        "gnomekeyring",
+        "kwallet",
+        "kwallet5",
+        "kwallet6",
+    ];
+
+    let head = value.split(':').next().unwrap_or(value);
+    let (browser, keyring) = match head.split_once('+') {
+        Some((browser, keyring)) => (browser, Some(keyring)),
+        None => (head, None),
+    };
+
+    if !BROWSERS.contains(&browser.to_ascii_lowercase().as_str()) {
+        return Err(format!(
+            "unsupported browser `{browser}`, expected one of: {}",
+            BROWSERS.join(", ")
+        ));
+    }
+    if let Some(keyring) = keyring {
+        if !KEYRINGS.contains(&keyring.to_ascii_lowercase().as_str()) {
+            return Err(format!(
+                "unsupported keyring `{keyring}`, expected one of: {}",
+                KEYRINGS.join(", ")
+            ));
+        }
+    }
+
+    Ok(value.to_string())
+}
+
+/// Time range of the media to download, in seconds
+#[derive(Debug, Clone, Copy)]
+pub struct Section {
+    pub start: f64,
+    /// `None` for the end of the media
+    pub end: Option<f64>,
+}
+
+impl Section {
+    /// Spec for yt-dlp's `--download-sections`
+    pub fn spec(&self) -> String {
+        match self.end {
+            Some(end) => format!("*{}-{}", self.start, end),
+            None => format!("*{}-inf", self.start),
+        }
+    }
+}
+
+/// Parse a time range, like yt-dlp (`10:00-20:00`, `*1:02:03-inf`, `90-120.5`)
+pub fn section(value: &str) -> Result<Section, String> {
+    let invalid =
+        || format!("invalid section `{value}`, expected e.g. `10:00-20:00` or `1:02:03-inf`");
+
+    let timestamp = |timestamp: &str| {
+        timestamp
+            .split(':')
+            .enumerate()
+            .try_fold(0.0, |total, (i, part)| {
+                let part = part
+                    .parse::<f64>()
+                    .ok()
+                    .filter(|p| p.is_finite() && *p >= 0.0)
+                    .ok_or_else(invalid)?;
+                // only the leading hours, minutes or seconds can exceed 59
+                if i > 0 && part >= 60.0 {
+                    return Err(format!(
+                        "invalid minutes or seconds `{part}` in the section `{value}`, expected below 60"
+                    ));
+                }
+                Ok(total * 60.0 + part)
+            })
+    };
+
+    let (start, end) = value
+        .strip_prefix('*')
+        .unwrap_or(value)
+        .split_once('-')
+        .ok_or_else(invalid)?;
+    let start = timestamp(start)?;
+    let end = match end {
+        "inf" | "" => None,
+        end => Some(timestamp(end)?),
+    };
+
+    if end.is_some_and(|end| end <= start) {
+        return Err(format!("the section `{value}` ends before it starts"));
+    }
+
+    Ok(Section { start, end })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn filesizes() {
+        assert_eq!(filesize("500"), Ok(500));
+        assert_eq!(filesize("500b"), Ok(500));
+        assert_eq!(filesize("50M"), Ok(50 << 20));
+        assert_eq!(filesize("500MB"), Ok(500_000_000));
+        assert_eq!(filesize("1.5GiB"), Ok(3 << 29));
+        assert_eq!(filesize("1.5kb"), Ok(1_500));
+
+        assert!(filesize("1.5").is_err());
+        assert!(filesize("").is_err());
+        assert!(filesize("MB").is_err());
+        assert!(filesize("1.2.3M").is_err());
+        assert!(filesize("12 MB").is_err());
+        assert!(filesize("10PB").is_err());
+    }
+
+    #[test]
+    fn sections() {
+        let spec = |value| section(value).map(|section| section.spec());
+        assert_eq!(spec("10:00-20:00"), Ok("*600-1200".to_string()));
+        assert_eq!(spec("*1:02:03-inf"), Ok("*3723-inf".to_string()));
+        assert_eq!(spec("90-120.5"), Ok("*90-120.5".to_string()));
+        assert_eq!(spec("90:00-"), Ok("*5400-inf".to_string()));
+
+        assert!(section("").is_err());
+        assert!(section("10:00").is_err());
+        assert!(section("a-b").is_err());
+        assert!(section("-1-2").is_err());
+        assert!(section("20:00-10:00").is_err_and(|err| err.contains("ends before it starts")));
+        assert!(section("1:70-2:00").is_err_and(|err| err.contains("invalid minutes")));
+        assert!(section("0-1:00:60").is_err_and(|err| err.contains("invalid minutes")));
+    }
+
+    #[test]
+    fn title_templates() {
+        for template in [
+            "%(title)s",
+            "%(uploader)s - %(title)s.%(ext)s",
+            "%(playlist_index)03d %(title)s.%(ext)s",
+            "100%% %(title)s",
+            "%(title)s [%(upload_date>%Y-%m-%d)s].%(ext)s",
+        ] {
+            assert_eq!(title_template(template).as_deref(), Ok(template));
+        }
+
+        assert!(title_template("%(id)s.%(ext)s").is_err_and(|err| err.contains("missing")));
+        assert!(title_template("%(title)").is_err());
+        assert!(title_template("%(title)s 100%").is_err());
+        assert!(title_template("%(title").is_err());
+        assert!(title_template("%()s %(title)s").is_err());
+        assert!(title_template("{title}").is_err());
+    }
+
+    #[test]
+    fn rates() {
+        for value in ["500", "50K", "4.2M", "1g"] {
+            assert_eq!(rate(value).as_deref(), Ok(value));
+        }
+
+        for value in ["", "K", "50KM", "50KB", "-1M", "1.2.3M", "fast"] {
+            assert!(rate(value).is_err(), "{value}");
+        }
+    }
+
+    #[test]
+    fn format_sorts() {
+        for value in [
+            "res",
+            "res,fps",
+            "+size,vcodec:av01",
+            "res:720,filesize~50M",
+        ] {
+            assert_eq!(format_sort(value).as_deref(), Ok(value));
+        }
+
+        for value in ["", "resolution", "res,", "res, fps"] {
+            assert!(format_sort(value).is_err(), "{value}");
+        }
+    }
+
+    #[test]
+    fn retry_sleeps() {
+        for value in [
+            "3",
+            "1.5",
+            "linear=1::2",
+            "exp=1:20",
+            "fragment:exp=1:20",
+            "http:5",
+        ] {
+            assert_eq!(retry_sleep(value).as_deref(), Ok(value));
+        }
+
+        for value in ["", "fast", "sqrt=1:2", "network:3", "linear=a", "http:"] {
+            assert!(retry_sleep(value).is_err(), "{value}");
+        }
+    }
+}
@@ -0,0 +1,142 @@
+//! Queue of the urls of a batch, persisted to be resumed with `--resume-queue`
+
+use std::path::PathBuf;
+
+use anyhow::Context;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Status {
+    Pending,
+    Done,
+    Failed,
+}
+
+impl Status {
+    fn name(self) -> &'static str {
+        match self {
+            Status::Pending => "pending",
+            Status::Done => "done",
+            Status::Failed => "failed",
+        }
+    }
+}
+
+/// Urls of the batch with their status, saved as one `status url` line each
+///
+/// The file can be edited: bare urls are pending, blank and `#` lines are ignored.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Queue {
+    pub entries: Vec<(Status, String)>,
+}
+
+impl Queue {
+    fn path() -> Option<PathBuf> {
+        dirs::data_local_dir().map(|dir| dir.join("md").join("queue.txt"))
+    }
+
+    /// Queue of the urls, all pending
+    pub fn new(urls: &[String]) -> Queue {
+        Queue {
+            entries: urls
+                .iter()
+                .map(|url| (Status::Pending, url.clone()))
+                .collect(),
+        }
+    }
+
+    /// Load the saved queue, `None` when there is none
+    pub fn load() -> Result<Option<Queue>, anyhow::Error> {
+        let Some(path) = Self::path() else {
+            return Ok(None);
+        };
+        match std::fs::read(&path) {
+            Ok(queue) => Ok(Some(Queue::parse(&String::from_utf8_lossy(&queue)))),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(err) => {
+                Err(err).with_context(|| format!("unable to read the queue: {}", path.display()))
+            }
+        }
+    }
+
+    fn parse(queue: &str) -> Queue {
+        let entries = queue
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(|line| {
+                let status = match line.split_once(char::is_whitespace) {
+                    Some(("pending", url)) => Some((Status::Pending, url)),
+                    Some(("done", url)) => Some((Status::Done, url)),
+                    Some(("failed", url)) => Some((Status::Failed, url)),
+                    _ => None,
+                };
+                let (status, url) = status.unwrap_or((Status::Pending, line));
+                (status, url.trim().to_string())
+            })
+            .collect();
+        Queue { entries }
+    }
+
+    /// Save the queue in the data directory
+    pub fn save(&self) -> Result<(), anyhow::Error> {
+        let Some(path) = Self::path() else {
+            return Ok(());
+        };
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let queue: String = self
+            .entries
+            .iter()
+            .map(|(status, url)| format!("{} {url}\n", status.name()))
+            .collect();
+        std::fs::write(path, queue)?;
+        Ok(())
+    }
+
+    /// Remove the saved queue, once all its urls were processed
+    pub fn clear() -> Result<(), anyhow::Error> {
+        let Some(path) = Self::path() else {
+            return Ok(());
+        };
+        match std::fs::remove_file(path) {
+            Err(err) if err.kind() != std::io::ErrorKind::NotFound => Err(err.into()),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn edited_queue_is_parsed() {
+        let queue = Queue::parse(
+            "done https://example.org/1\n\
+             # added by hand\n\
+             \n\
+             https://example.org/2\n\
+             failed   https://example.org/3\n\
+             pending https://example.org/4\n\
+             skipped https://example.org/5\n",
+        );
+
+        assert_eq!(
+            queue.entries,
+            [
+                (Status::Done, "https://example.org/1".to_string()),
+                (Status::Pending, "https://example.org/2".to_string()),
+                (Status::Failed, "https://example.org/3".to_string()),
+                (Status::Pending, "https://example.org/4".to_string()),
+                (Status::Pending, "skipped https://example.org/5".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn corrupt_queue_is_parsed() {
+        let queue = Queue::parse(&String::from_utf8_lossy(b"done \xff\xfe\n\0\0\n"));
+        assert_eq!(queue.entries.len(), 2);
+    }
+}
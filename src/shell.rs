@@ -0,0 +1,26 @@
+//! Shell quoting
+
+use std::borrow::Cow;
+use std::ffi::OsStr;
+use std::process::Command;
+
+/// Quote an argument for a POSIX shell, leaving simple ones untouched
+pub fn quote(arg: &OsStr) -> Cow<'_, str> {
+    let arg = arg.to_string_lossy();
+
+    let is_safe = |c: char| c.is_ascii_alphanumeric() || "_-./:=+,@%".contains(c);
+    if !arg.is_empty() && arg.chars().all(is_safe) {
+        arg
+    } else {
+        format!("'{}'", arg.replace('\'', r"'\''")).into()
+    }
+}
+
+/// Render the command as a shell-quoted one-liner
+pub fn command_line(command: &Command) -> String {
+    std::iter::once(command.get_program())
+        .chain(command.get_args())
+        .map(quote)
+        .collect::<Vec<_>>()
+        .join(" ")
+}